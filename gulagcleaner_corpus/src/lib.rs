@@ -0,0 +1,423 @@
+/*!
+ Regression sweeps of Gulag Cleaner over a local corpus of PDFs.
+
+ Every PDF under the corpus directory is cleaned and checked against the expectations in a
+ manifest. The outcome of each file is saved, so the next run can flag the files whose
+ outcome changed.
+
+ The manifest is a JSON object keyed by the path of each file, relative to the corpus
+ directory. Fields left out of an expectation are not checked:
+
+ ```json
+ {
+     "wuolah/tema1.pdf": { "method": "Wuolah", "pages": 12, "verifies": true },
+     "broken.pdf": { "error": "PARSE_ERROR" }
+ }
+ ```
+*/
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use gulagcleaner_rs::report::CleanReport;
+use gulagcleaner_rs::verify::VerifyStatus;
+use gulagcleaner_rs::{clean_pdf_with_report, CleanOptions};
+use serde::{Deserialize, Serialize};
+
+/// Command line of the corpus runner.
+#[derive(Debug, Clone, clap::Parser)]
+#[command(
+    version,
+    about = "Cleans every PDF in a directory and checks the results against a manifest."
+)]
+pub struct Config {
+    /// Directory with the PDFs to clean. Subdirectories are included.
+    pub dir: PathBuf,
+    /// Expected outcomes of the files. Defaults to `manifest.json` in the corpus directory.
+    #[arg(long)]
+    pub manifest: Option<PathBuf>,
+    /// Where the outcomes are saved for the next run. Defaults to `last-run.json` in the
+    /// corpus directory.
+    #[arg(long)]
+    pub last_run: Option<PathBuf>,
+    /// Use the naive cleaning method for every file.
+    #[arg(long)]
+    pub force_naive: bool,
+}
+
+impl Config {
+    fn manifest_path(&self) -> PathBuf {
+        self.manifest
+            .clone()
+            .unwrap_or_else(|| self.dir.join("manifest.json"))
+    }
+
+    fn last_run_path(&self) -> PathBuf {
+        self.last_run
+            .clone()
+            .unwrap_or_else(|| self.dir.join("last-run.json"))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+/// What a file of the corpus is expected to clean to. Fields that are `None` aren't checked.
+pub struct Expectation {
+    /// The cleaning method: "Wuolah", "StuDocu" or "Naive".
+    #[serde(default)]
+    pub method: Option<String>,
+    /// The number of pages after cleaning.
+    #[serde(default)]
+    pub pages: Option<u32>,
+    /// Whether the cleaned document must pass verification without any issue.
+    #[serde(default)]
+    pub verifies: Option<bool>,
+    /// The error code of a file that must fail to clean, as in "PARSE_ERROR".
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// The expectations of the corpus, keyed by the path of each file relative to the corpus
+/// directory.
+pub type Manifest = BTreeMap<String, Expectation>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "result")]
+/// What cleaning a file gave.
+pub enum Outcome {
+    Cleaned {
+        method: String,
+        pages: u32,
+        status: VerifyStatus,
+    },
+    Failed {
+        /// The error code, as in "PARSE_ERROR".
+        error: String,
+    },
+    /// The cleaner panicked. This is a bug, so it never meets an expectation.
+    Panicked,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Cleaned {
+                method,
+                pages,
+                status,
+            } => write!(f, "{}, {} pages, {:?}", method, pages, status),
+            Outcome::Failed { error } => write!(f, "{}", error),
+            Outcome::Panicked => write!(f, "PANIC"),
+        }
+    }
+}
+
+/// The result of a single file of the corpus.
+#[derive(Debug, Clone)]
+pub struct FileResult {
+    /// The path of the file, relative to the corpus directory.
+    pub path: String,
+    pub outcome: Outcome,
+    /// The error message, if the file could not be cleaned.
+    pub message: Option<String>,
+    pub duration: Duration,
+    /// The expectations the outcome doesn't meet, or `None` if the file is not in the
+    /// manifest.
+    pub failures: Option<Vec<String>>,
+    /// The outcome of the previous run, if it was different.
+    pub previous: Option<Outcome>,
+}
+
+impl FileResult {
+    /// Whether the file meets its expectations. Files without any pass.
+    pub fn passed(&self) -> bool {
+        self.failures.as_ref().is_none_or(Vec::is_empty)
+    }
+}
+
+/// The results of a run over the corpus.
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub results: Vec<FileResult>,
+    /// Files listed in the manifest that are not in the corpus directory.
+    pub missing: Vec<String>,
+}
+
+impl Summary {
+    /// Whether every file met its expectations and no file of the manifest is missing.
+    pub fn passed(&self) -> bool {
+        self.missing.is_empty() && self.results.iter().all(FileResult::passed)
+    }
+}
+
+/// Finds the PDFs under `dir`.
+///
+/// # Returns
+///
+/// The paths of the files relative to `dir`, with `/` as the separator, in sorted order.
+pub fn find_pdfs(dir: &Path) -> io::Result<Vec<String>> {
+    fn walk(dir: &Path, prefix: &str, pdfs: &mut Vec<String>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = format!("{}{}", prefix, name);
+            if entry.file_type()?.is_dir() {
+                walk(&entry.path(), &format!("{}/", path), pdfs)?;
+            } else if name.to_lowercase().ends_with(".pdf") {
+                pdfs.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut pdfs = Vec::new();
+    walk(dir, "", &mut pdfs)?;
+    pdfs.sort();
+    Ok(pdfs)
+}
+
+/// Cleans the file at `path`.
+///
+/// A panic while cleaning is caught, so that one file can't stop the run.
+///
+/// # Returns
+///
+/// The outcome, and the error message if the file could not be cleaned.
+pub fn clean_file(path: &Path, options: &CleanOptions) -> (Outcome, Option<String>) {
+    outcome_of(|| {
+        let data = fs::read(path).map_err(gulagcleaner_rs::Error::Io)?;
+        clean_pdf_with_report(&data, options).map(|(_, report)| report)
+    })
+}
+
+/// Runs `clean`, turning its result or panic into an outcome.
+fn outcome_of(
+    clean: impl FnOnce() -> gulagcleaner_rs::Result<CleanReport>,
+) -> (Outcome, Option<String>) {
+    let result = match panic::catch_unwind(AssertUnwindSafe(clean)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|x| x.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            return (Outcome::Panicked, Some(format!("panicked: {}", message)));
+        }
+    };
+    match result {
+        Ok(report) => (
+            Outcome::Cleaned {
+                method: report.method_name().to_string(),
+                pages: report.pages_after,
+                status: report.status(),
+            },
+            None,
+        ),
+        Err(e) => (
+            Outcome::Failed {
+                error: e.code().to_string(),
+            },
+            Some(e.to_string()),
+        ),
+    }
+}
+
+/// Checks an outcome against its expectation.
+///
+/// # Returns
+///
+/// A description of every expectation the outcome doesn't meet.
+pub fn check(expectation: &Expectation, outcome: &Outcome) -> Vec<String> {
+    let mut failures = Vec::new();
+    match outcome {
+        Outcome::Cleaned {
+            method,
+            pages,
+            status,
+        } => {
+            if let Some(error) = &expectation.error {
+                failures.push(format!("expected error {}, but it cleaned", error));
+            }
+            if let Some(expected) = &expectation.method {
+                if expected != method {
+                    failures.push(format!("expected method {}, got {}", expected, method));
+                }
+            }
+            if let Some(expected) = expectation.pages {
+                if expected != *pages {
+                    failures.push(format!("expected {} pages, got {}", expected, pages));
+                }
+            }
+            if let Some(verifies) = expectation.verifies {
+                if verifies != (*status == VerifyStatus::Passed) {
+                    failures.push(format!(
+                        "expected it {}to verify, got {:?}",
+                        if verifies { "" } else { "not " },
+                        status
+                    ));
+                }
+            }
+        }
+        Outcome::Failed { error } => match &expectation.error {
+            Some(expected) if expected == error => {}
+            Some(expected) => failures.push(format!("expected error {}, got {}", expected, error)),
+            None => failures.push(format!("failed with {}", error)),
+        },
+        Outcome::Panicked => failures.push("panicked".to_string()),
+    }
+    failures
+}
+
+/// Reads a JSON file, or returns the default value if the file does not exist.
+fn read_json<T: Default + serde::de::DeserializeOwned>(path: &Path) -> Result<T, Box<dyn Error>> {
+    match fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data)
+            .map_err(|e| format!("`{}` is not valid: {}", path.display(), e).into()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(format!("failed to read `{}`: {}", path.display(), e).into()),
+    }
+}
+
+/// Cleans every PDF of the corpus, checks them against the manifest and saves the outcomes
+/// for the next run.
+///
+/// # Returns
+///
+/// The results of the run, or an error if the corpus, the manifest or the outcomes of the
+/// last run can't be read, or the new outcomes can't be saved.
+pub fn run(config: &Config) -> Result<Summary, Box<dyn Error>> {
+    let manifest: Manifest = read_json(&config.manifest_path())?;
+    let last_run_path = config.last_run_path();
+    let last_run: BTreeMap<String, Outcome> = read_json(&last_run_path)?;
+    let options = CleanOptions {
+        force_naive: config.force_naive,
+        ..Default::default()
+    };
+
+    let pdfs = find_pdfs(&config.dir)
+        .map_err(|e| format!("failed to read `{}`: {}", config.dir.display(), e))?;
+    let mut summary = Summary::default();
+    for path in &pdfs {
+        let start = Instant::now();
+        let (outcome, message) = clean_file(&config.dir.join(path), &options);
+        let duration = start.elapsed();
+
+        // A panic fails the run even for files without expectations.
+        let failures = match (manifest.get(path), &outcome) {
+            (Some(expectation), _) => Some(check(expectation, &outcome)),
+            (None, Outcome::Panicked) => Some(check(&Expectation::default(), &outcome)),
+            (None, _) => None,
+        };
+        let previous = last_run.get(path).filter(|x| **x != outcome).cloned();
+        summary.results.push(FileResult {
+            path: path.clone(),
+            outcome,
+            message,
+            duration,
+            failures,
+            previous,
+        });
+    }
+    summary.missing = manifest
+        .keys()
+        .filter(|x| !pdfs.contains(x))
+        .cloned()
+        .collect();
+
+    let outcomes: BTreeMap<&String, &Outcome> = summary
+        .results
+        .iter()
+        .map(|x| (&x.path, &x.outcome))
+        .collect();
+    fs::write(&last_run_path, serde_json::to_vec_pretty(&outcomes)?)
+        .map_err(|e| format!("failed to write `{}`: {}", last_run_path.display(), e))?;
+    Ok(summary)
+}
+
+/// Prints the results of a run as a table, followed by the failures and a summary line.
+pub fn print_table(summary: &Summary, out: &mut impl Write) -> io::Result<()> {
+    let width = summary
+        .results
+        .iter()
+        .map(|x| x.path.chars().count())
+        .chain([4])
+        .max()
+        .unwrap_or(4);
+    writeln!(
+        out,
+        "{:width$}  {:8}  {:>5}  {:18}  {:>9}  RESULT",
+        "FILE", "METHOD", "PAGES", "STATUS", "TIME"
+    )?;
+    for result in &summary.results {
+        let (method, pages, status) = match &result.outcome {
+            Outcome::Cleaned {
+                method,
+                pages,
+                status,
+            } => (method.as_str(), pages.to_string(), format!("{:?}", status)),
+            Outcome::Failed { error } => ("-", "-".to_string(), error.clone()),
+            Outcome::Panicked => ("-", "-".to_string(), result.outcome.to_string()),
+        };
+        let verdict = match &result.failures {
+            None => "-",
+            Some(_) if result.passed() => "PASS",
+            Some(_) => "FAIL",
+        };
+        write!(
+            out,
+            "{:width$}  {:8}  {:>5}  {:18}  {:>6} ms  {}",
+            result.path,
+            method,
+            pages,
+            status,
+            result.duration.as_millis(),
+            verdict
+        )?;
+        match &result.previous {
+            Some(previous) => writeln!(out, "  CHANGED (was {})", previous)?,
+            None => writeln!(out)?,
+        }
+    }
+
+    let mut problems = Vec::new();
+    for result in &summary.results {
+        for failure in result.failures.iter().flatten() {
+            problems.push(format!("{}: {}", result.path, failure));
+        }
+        if let (false, Some(message)) = (result.passed(), &result.message) {
+            problems.push(format!("{}: {}", result.path, message));
+        }
+    }
+    for path in &summary.missing {
+        problems.push(format!("{}: listed in the manifest but not found", path));
+    }
+    if !problems.is_empty() {
+        writeln!(out)?;
+        for problem in problems {
+            writeln!(out, "{}", problem)?;
+        }
+    }
+
+    let count = |f: fn(&FileResult) -> bool| summary.results.iter().filter(|x| f(x)).count();
+    let total: Duration = summary.results.iter().map(|x| x.duration).sum();
+    writeln!(
+        out,
+        "\n{} files: {} passed, {} failed, {} without expectations, {} changed, {} missing, in {:.1} s",
+        summary.results.len(),
+        count(|x| x.failures.is_some() && x.passed()),
+        count(|x| !x.passed()),
+        count(|x| x.failures.is_none()),
+        count(|x| x.previous.is_some()),
+        summary.missing.len(),
+        total.as_secs_f32()
+    )
+}
+
+#[cfg(test)]
+mod tests;
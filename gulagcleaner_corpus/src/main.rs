@@ -0,0 +1,26 @@
+use std::io;
+use std::process::ExitCode;
+
+use clap::Parser;
+use gulagcleaner_corpus::{print_table, run, Config};
+
+fn main() -> ExitCode {
+    let config = Config::parse();
+    let summary = match run(&config) {
+        Ok(summary) => summary,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    if let Err(e) = print_table(&summary, &mut io::stdout().lock()) {
+        eprintln!("{}", e);
+        return ExitCode::from(2);
+    }
+    if summary.passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{check, find_pdfs, outcome_of, print_table, run, Config, Expectation, Outcome};
+use gulagcleaner_rs::fixtures;
+use gulagcleaner_rs::verify::VerifyStatus;
+use std::fs;
+use std::path::Path;
+
+/// Writes a corpus with a Wuolah and a StuDocu download, and a file that isn't a PDF.
+fn write_corpus(dir: &Path) {
+    fs::create_dir(dir.join("wuolah")).unwrap();
+    fs::write(
+        dir.join("wuolah/tema1.pdf"),
+        fixtures::to_bytes(&mut fixtures::wuolah(3)),
+    )
+    .unwrap();
+    fs::write(
+        dir.join("studocu.PDF"),
+        fixtures::to_bytes(&mut fixtures::studocu(2)),
+    )
+    .unwrap();
+    fs::write(dir.join("broken.pdf"), b"not a pdf").unwrap();
+    fs::write(dir.join("notes.txt"), b"not even a pdf name").unwrap();
+}
+
+fn config(dir: &Path) -> Config {
+    Config {
+        dir: dir.to_path_buf(),
+        manifest: None,
+        last_run: None,
+        force_naive: false,
+    }
+}
+
+#[test]
+fn test_find_pdfs() {
+    let dir = tempfile::tempdir().unwrap();
+    write_corpus(dir.path());
+    assert_eq!(
+        find_pdfs(dir.path()).unwrap(),
+        vec!["broken.pdf", "studocu.PDF", "wuolah/tema1.pdf"]
+    );
+}
+
+#[test]
+fn test_check() {
+    let outcome = Outcome::Cleaned {
+        method: "Wuolah".to_string(),
+        pages: 3,
+        status: VerifyStatus::Passed,
+    };
+    let expectation = Expectation {
+        method: Some("Wuolah".to_string()),
+        pages: Some(3),
+        verifies: Some(true),
+        error: None,
+    };
+    assert!(check(&expectation, &outcome).is_empty());
+    assert!(check(&Expectation::default(), &outcome).is_empty());
+
+    let expectation = Expectation {
+        method: Some("StuDocu".to_string()),
+        pages: Some(4),
+        ..Default::default()
+    };
+    assert_eq!(check(&expectation, &outcome).len(), 2);
+
+    let failed = Outcome::Failed {
+        error: "PARSE_ERROR".to_string(),
+    };
+    let expectation = Expectation {
+        error: Some("PARSE_ERROR".to_string()),
+        ..Default::default()
+    };
+    assert!(check(&expectation, &failed).is_empty());
+    assert_eq!(check(&Expectation::default(), &failed).len(), 1);
+    assert_eq!(check(&expectation, &outcome).len(), 1);
+    assert_eq!(check(&expectation, &Outcome::Panicked).len(), 1);
+}
+
+#[test]
+fn test_panic_is_an_outcome() {
+    let (outcome, message) = outcome_of(|| panic!("page {} is broken", 3));
+    assert_eq!(outcome, Outcome::Panicked);
+    assert_eq!(message.unwrap(), "panicked: page 3 is broken");
+    assert_eq!(outcome.to_string(), "PANIC");
+
+    let (outcome, _) = outcome_of(|| Err(gulagcleaner_rs::Error::Cancelled));
+    assert_eq!(
+        outcome,
+        Outcome::Failed {
+            error: "CANCELLED".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_run_checks_manifest_and_flags_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    write_corpus(dir.path());
+    let manifest = serde_json::json!({
+        "wuolah/tema1.pdf": { "method": "Wuolah", "pages": 3, "verifies": true },
+        "studocu.PDF": { "method": "StuDocu", "pages": 5 },
+        "broken.pdf": { "error": "PARSE_ERROR" },
+        "gone.pdf": {},
+    });
+    fs::write(dir.path().join("manifest.json"), manifest.to_string()).unwrap();
+
+    let summary = run(&config(dir.path())).unwrap();
+    let passed: Vec<(&str, bool)> = summary
+        .results
+        .iter()
+        .map(|x| (x.path.as_str(), x.passed()))
+        .collect();
+    assert_eq!(
+        passed,
+        vec![
+            ("broken.pdf", true),
+            ("studocu.PDF", false),
+            ("wuolah/tema1.pdf", true)
+        ]
+    );
+    assert_eq!(summary.missing, vec!["gone.pdf"]);
+    assert!(!summary.passed());
+    assert!(summary.results.iter().all(|x| x.previous.is_none()));
+
+    let mut table = Vec::new();
+    print_table(&summary, &mut table).unwrap();
+    let table = String::from_utf8(table).unwrap();
+    assert!(table.contains("studocu.PDF: expected 5 pages, got 2"));
+    assert!(table.contains("gone.pdf: listed in the manifest but not found"));
+
+    // The next run compares against the outcomes saved by this one
+    fs::write(
+        dir.path().join("wuolah/tema1.pdf"),
+        fixtures::to_bytes(&mut fixtures::studocu(1)),
+    )
+    .unwrap();
+    let summary = run(&config(dir.path())).unwrap();
+    let changed: Vec<&str> = summary
+        .results
+        .iter()
+        .filter(|x| x.previous.is_some())
+        .map(|x| x.path.as_str())
+        .collect();
+    assert_eq!(changed, vec!["wuolah/tema1.pdf"]);
+
+    let mut table = Vec::new();
+    print_table(&summary, &mut table).unwrap();
+    let table = String::from_utf8(table).unwrap();
+    assert!(table.contains("CHANGED (was Wuolah, 3 pages, Passed)"));
+}
+
+#[test]
+fn test_invalid_manifest_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    write_corpus(dir.path());
+    fs::write(
+        dir.path().join("manifest.json"),
+        r#"{ "broken.pdf": { "eror": "PARSE_ERROR" } }"#,
+    )
+    .unwrap();
+    let error = run(&config(dir.path())).unwrap_err();
+    assert!(error.to_string().contains("manifest.json"));
+}
@@ -0,0 +1,18 @@
+use std::env;
+
+/// Generates the C header from the `extern "C"` functions of the crate into `OUT_DIR`.
+///
+/// The copy in `include/gulagcleaner.h` is committed, and the `test_header_is_up_to_date`
+/// test checks that it matches.
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = env::var("OUT_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+        .expect("Failed to read cbindgen.toml");
+    cbindgen::generate_with_config(&crate_dir, config)
+        .expect("Failed to generate the C header")
+        .write_to_file(format!("{}/gulagcleaner.h", out_dir));
+}
@@ -0,0 +1,96 @@
+#ifndef GULAGCLEANER_H
+#define GULAGCLEANER_H
+
+/* This file is generated by cbindgen from gulagcleaner_ffi. Do not edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The result of a call into the library.
+typedef enum GulagStatus {
+  GULAG_STATUS_OK = 0,
+  // The input could not be parsed as a PDF document.
+  GULAG_STATUS_PARSE_ERROR = 1,
+  // The document is protected with a user password.
+  GULAG_STATUS_ENCRYPTED_DOCUMENT = 2,
+  // The document parsed, but can't be cleaned.
+  GULAG_STATUS_UNSUPPORTED_DOCUMENT = 3,
+  // The cleaned document could not be written.
+  GULAG_STATUS_SAVE_ERROR = 4,
+  GULAG_STATUS_IO_ERROR = 5,
+  // The cover page doesn't have the expected metadata.
+  GULAG_STATUS_METADATA_NOT_FOUND = 6,
+  GULAG_STATUS_CANCELLED = 7,
+  // A required pointer was null, or the options are not valid.
+  GULAG_STATUS_INVALID_ARGUMENT = 8,
+  // The library panicked. This is a bug.
+  GULAG_STATUS_INTERNAL_ERROR = 9,
+  // The document goes over the limits in the options.
+  GULAG_STATUS_LIMIT_EXCEEDED = 10,
+} GulagStatus;
+
+// A byte buffer owned by the library. Release it with `gulag_buffer_free`.
+typedef struct GulagBuffer {
+  uint8_t *data;
+  size_t len;
+} GulagBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Cleans the PDF document in `data`.
+//
+// `options_json` is a JSON object with the fields of `CleanOptions`, such as
+// `{"force_naive": true}`. It may be null to use the defaults.
+//
+// On success, `*out_pdf` holds the cleaned document and, if `out_report` is not null,
+// `*out_report` holds the `CleanReport` as a JSON string. Nothing is written on failure.
+//
+// # Safety
+//
+// `data` must point to `len` readable bytes, `options_json` must be null or a NUL-terminated
+// string, and `out_pdf` and `out_report` must be null or valid for writes.
+enum GulagStatus gulag_clean(const uint8_t *data,
+                             size_t len,
+                             const char *options_json,
+                             struct GulagBuffer *out_pdf,
+                             char **out_report);
+
+// Detects the cleaning method of the PDF document in `data` and classifies its pages, without
+// cleaning it.
+//
+// On success, `*out_analysis` holds the `Analysis` as a JSON string.
+//
+// # Safety
+//
+// `data` must point to `len` readable bytes, and `out_analysis` must be valid for writes.
+enum GulagStatus gulag_analyze(const uint8_t *data, size_t len, char **out_analysis);
+
+// The message of the last error on the calling thread, or null if the last call succeeded.
+//
+// The string stays valid until the next call into the library on the same thread.
+const char *gulag_last_error(void);
+
+// Releases a buffer returned by the library. Freeing an empty buffer does nothing.
+//
+// # Safety
+//
+// `buffer` must have been returned by the library and not freed before.
+void gulag_buffer_free(struct GulagBuffer buffer);
+
+// Releases a string returned by the library. Freeing null does nothing.
+//
+// # Safety
+//
+// `string` must have been returned by the library and not freed before.
+void gulag_string_free(char *string);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* GULAGCLEANER_H */
@@ -0,0 +1,238 @@
+/*!
+ C ABI for Gulag Cleaner, for applications that can't use the Rust, Python or WASM packages.
+
+ The header is `include/gulagcleaner.h`. It is generated when the crate is built, and the
+ committed copy is checked against it by the tests. Every function
+ returns a `GulagStatus`; on failure, `gulag_last_error` describes what went wrong. Buffers and
+ strings returned by the library must be released with `gulag_buffer_free` and
+ `gulag_string_free`.
+*/
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use gulagcleaner_rs::{CleanOptions, Error};
+
+/// The result of a call into the library.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GulagStatus {
+    Ok = 0,
+    /// The input could not be parsed as a PDF document.
+    ParseError = 1,
+    /// The document is protected with a user password.
+    EncryptedDocument = 2,
+    /// The document parsed, but can't be cleaned.
+    UnsupportedDocument = 3,
+    /// The cleaned document could not be written.
+    SaveError = 4,
+    IoError = 5,
+    /// The cover page doesn't have the expected metadata.
+    MetadataNotFound = 6,
+    Cancelled = 7,
+    /// A required pointer was null, or the options are not valid.
+    InvalidArgument = 8,
+    /// The library panicked. This is a bug.
+    InternalError = 9,
+    /// The document goes over the limits in the options.
+    LimitExceeded = 10,
+}
+
+impl From<&Error> for GulagStatus {
+    fn from(e: &Error) -> Self {
+        match e {
+            Error::Parse(_) => GulagStatus::ParseError,
+            Error::Encrypted(_) => GulagStatus::EncryptedDocument,
+            Error::Unsupported(_) => GulagStatus::UnsupportedDocument,
+            Error::Save(_) => GulagStatus::SaveError,
+            Error::Io(_) => GulagStatus::IoError,
+            Error::MissingMetadata { .. } => GulagStatus::MetadataNotFound,
+            Error::Cancelled => GulagStatus::Cancelled,
+            Error::LimitExceeded { .. } => GulagStatus::LimitExceeded,
+            Error::InvalidOptions(_) => GulagStatus::InvalidArgument,
+        }
+    }
+}
+
+/// A byte buffer owned by the library. Release it with `gulag_buffer_free`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct GulagBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl GulagBuffer {
+    fn from_vec(data: Vec<u8>) -> GulagBuffer {
+        let data = Box::into_raw(data.into_boxed_slice());
+        GulagBuffer {
+            data: data as *mut u8,
+            len: data.len(),
+        }
+    }
+}
+
+type Failure = (GulagStatus, String);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, catching panics and storing the error message for `gulag_last_error`.
+fn run(f: impl FnOnce() -> Result<(), Failure>) -> GulagStatus {
+    let result = panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        let reason = panic
+            .downcast_ref::<&str>()
+            .map(|x| x.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        Err((
+            GulagStatus::InternalError,
+            format!("internal error: {}", reason),
+        ))
+    });
+
+    let (status, message) = match result {
+        Ok(()) => (GulagStatus::Ok, None),
+        Err((status, message)) => (status, Some(message)),
+    };
+    LAST_ERROR.with(|last| {
+        *last.borrow_mut() = message.map(|x| CString::new(x.replace('\0', "")).unwrap_or_default())
+    });
+    status
+}
+
+fn core_failure(e: Error) -> Failure {
+    (GulagStatus::from(&e), e.to_string())
+}
+
+fn invalid_argument(message: &str) -> Failure {
+    (GulagStatus::InvalidArgument, message.to_string())
+}
+
+/// Borrows the input buffer. A null pointer is only accepted for an empty buffer.
+unsafe fn input<'a>(data: *const u8, len: usize) -> Result<&'a [u8], Failure> {
+    if data.is_null() {
+        if len != 0 {
+            return Err(invalid_argument("data is null"));
+        }
+        return Ok(&[]);
+    }
+    Ok(std::slice::from_raw_parts(data, len))
+}
+
+fn json_string(value: &impl serde::Serialize) -> Result<*mut c_char, Failure> {
+    let json =
+        serde_json::to_string(value).map_err(|e| (GulagStatus::InternalError, e.to_string()))?;
+    let json = CString::new(json).map_err(|e| (GulagStatus::InternalError, e.to_string()))?;
+    Ok(json.into_raw())
+}
+
+/// Cleans the PDF document in `data`.
+///
+/// `options_json` is a JSON object with the fields of `CleanOptions`, such as
+/// `{"force_naive": true}`. It may be null to use the defaults.
+///
+/// On success, `*out_pdf` holds the cleaned document and, if `out_report` is not null,
+/// `*out_report` holds the `CleanReport` as a JSON string. Nothing is written on failure.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, `options_json` must be null or a NUL-terminated
+/// string, and `out_pdf` and `out_report` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn gulag_clean(
+    data: *const u8,
+    len: usize,
+    options_json: *const c_char,
+    out_pdf: *mut GulagBuffer,
+    out_report: *mut *mut c_char,
+) -> GulagStatus {
+    run(|| {
+        let data = input(data, len)?;
+        if out_pdf.is_null() {
+            return Err(invalid_argument("out_pdf is null"));
+        }
+        let options: CleanOptions = if options_json.is_null() {
+            CleanOptions::default()
+        } else {
+            let json = CStr::from_ptr(options_json)
+                .to_str()
+                .map_err(|_| invalid_argument("options_json is not UTF-8"))?;
+            serde_json::from_str(json)
+                .map_err(|e| invalid_argument(&format!("invalid options: {}", e)))?
+        };
+
+        let (clean_pdf, report) =
+            gulagcleaner_rs::clean_pdf_with_report(data, &options).map_err(core_failure)?;
+        if !out_report.is_null() {
+            *out_report = json_string(&report)?;
+        }
+        *out_pdf = GulagBuffer::from_vec(clean_pdf);
+        Ok(())
+    })
+}
+
+/// Detects the cleaning method of the PDF document in `data` and classifies its pages, without
+/// cleaning it.
+///
+/// On success, `*out_analysis` holds the `Analysis` as a JSON string.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, and `out_analysis` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn gulag_analyze(
+    data: *const u8,
+    len: usize,
+    out_analysis: *mut *mut c_char,
+) -> GulagStatus {
+    run(|| {
+        let data = input(data, len)?;
+        if out_analysis.is_null() {
+            return Err(invalid_argument("out_analysis is null"));
+        }
+        let doc = gulagcleaner_rs::load_document(data).map_err(core_failure)?;
+        *out_analysis = json_string(&gulagcleaner_rs::analyze_document(&doc))?;
+        Ok(())
+    })
+}
+
+/// The message of the last error on the calling thread, or null if the last call succeeded.
+///
+/// The string stays valid until the next call into the library on the same thread.
+#[no_mangle]
+pub extern "C" fn gulag_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |x| x.as_ptr()))
+}
+
+/// Releases a buffer returned by the library. Freeing an empty buffer does nothing.
+///
+/// # Safety
+///
+/// `buffer` must have been returned by the library and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn gulag_buffer_free(buffer: GulagBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+/// Releases a string returned by the library. Freeing null does nothing.
+///
+/// # Safety
+///
+/// `string` must have been returned by the library and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn gulag_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,115 @@
+use crate::*;
+use std::ffi::CStr;
+use std::fs;
+
+const STUDOCU_PATH: &str = "../gulagcleaner_rs/example_docs/studocu-example.pdf";
+
+fn last_error() -> String {
+    let error = gulag_last_error();
+    assert!(!error.is_null());
+    unsafe { CStr::from_ptr(error) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[test]
+fn test_clean_and_free() {
+    let data = fs::read(STUDOCU_PATH).expect("Failed to read PDF");
+    let options = CString::new(r#"{"force_naive": false}"#).unwrap();
+    let mut pdf = GulagBuffer {
+        data: ptr::null_mut(),
+        len: 0,
+    };
+    let mut report = ptr::null_mut();
+
+    let status = unsafe {
+        gulag_clean(
+            data.as_ptr(),
+            data.len(),
+            options.as_ptr(),
+            &mut pdf,
+            &mut report,
+        )
+    };
+    assert_eq!(status, GulagStatus::Ok);
+    assert!(gulag_last_error().is_null());
+
+    let cleaned = unsafe { std::slice::from_raw_parts(pdf.data, pdf.len) };
+    assert!(cleaned.starts_with(b"%PDF"));
+    let report_json = unsafe { CStr::from_ptr(report) }.to_str().unwrap();
+    let report_value: serde_json::Value = serde_json::from_str(report_json).unwrap();
+    assert_eq!(report_value["method_code"], 1);
+    assert_eq!(report_value["deleted_pages"], serde_json::json!([1]));
+
+    unsafe {
+        gulag_buffer_free(pdf);
+        gulag_string_free(report);
+    }
+}
+
+#[test]
+fn test_analyze() {
+    let data = fs::read(STUDOCU_PATH).expect("Failed to read PDF");
+    let mut analysis = ptr::null_mut();
+
+    let status = unsafe { gulag_analyze(data.as_ptr(), data.len(), &mut analysis) };
+    assert_eq!(status, GulagStatus::Ok);
+    let analysis_json = unsafe { CStr::from_ptr(analysis) }.to_str().unwrap();
+    let analysis_value: serde_json::Value = serde_json::from_str(analysis_json).unwrap();
+    assert_eq!(analysis_value["pages"][0]["delete"], true);
+    unsafe { gulag_string_free(analysis) };
+}
+
+#[test]
+fn test_errors_are_reported() {
+    let data = b"definitely not a pdf";
+    let mut pdf = GulagBuffer {
+        data: ptr::null_mut(),
+        len: 0,
+    };
+
+    let status = unsafe {
+        gulag_clean(
+            data.as_ptr(),
+            data.len(),
+            ptr::null(),
+            &mut pdf,
+            ptr::null_mut(),
+        )
+    };
+    assert_eq!(status, GulagStatus::ParseError);
+    assert!(last_error().starts_with("could not parse the document"));
+    assert!(pdf.data.is_null());
+
+    let options = CString::new(r#"{"force_naive": "yes"}"#).unwrap();
+    let status = unsafe {
+        gulag_clean(
+            data.as_ptr(),
+            data.len(),
+            options.as_ptr(),
+            &mut pdf,
+            ptr::null_mut(),
+        )
+    };
+    assert_eq!(status, GulagStatus::InvalidArgument);
+    assert!(last_error().starts_with("invalid options"));
+
+    let status = unsafe { gulag_analyze(ptr::null(), 10, ptr::null_mut()) };
+    assert_eq!(status, GulagStatus::InvalidArgument);
+}
+
+/// The committed header must match the one generated from the sources. Run the tests with
+/// `UPDATE_HEADER=1` to regenerate it.
+#[test]
+fn test_header_is_up_to_date() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/gulagcleaner.h"));
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/include/gulagcleaner.h");
+    if std::env::var_os("UPDATE_HEADER").is_some() {
+        fs::write(path, generated).unwrap();
+    }
+    let committed = fs::read_to_string(path).unwrap_or_default();
+    assert!(
+        committed == generated,
+        "include/gulagcleaner.h is out of date, run `UPDATE_HEADER=1 cargo test -p gulagcleaner_ffi`"
+    );
+}
@@ -0,0 +1,22 @@
+// Builds the addon with cargo and copies it next to index.js as `gulagcleaner.node`, along
+// with the report types shared with the WASM package.
+const { execFileSync } = require('child_process');
+const { copyFileSync } = require('fs');
+const path = require('path');
+
+const release = !process.argv.includes('--debug');
+execFileSync('cargo', ['build', '-p', 'gulagcleaner_node', ...(release ? ['--release'] : [])], {
+  stdio: 'inherit',
+});
+
+const library = {
+  win32: 'gulagcleaner_node.dll',
+  darwin: 'libgulagcleaner_node.dylib',
+}[process.platform] || 'libgulagcleaner_node.so';
+
+const target = path.join(__dirname, '..', 'target', release ? 'release' : 'debug', library);
+copyFileSync(target, path.join(__dirname, 'gulagcleaner.node'));
+copyFileSync(
+  path.join(__dirname, '..', 'gulagcleaner_wasm', 'src', 'report.d.ts'),
+  path.join(__dirname, 'report.d.ts'),
+);
@@ -0,0 +1,28 @@
+/// <reference types="node" />
+
+// The report and options types are shared with the WASM package.
+import type { CleanOptions, CleanReport } from "./report";
+export * from "./report";
+
+export interface CleaningResult {
+  /** The cleaned PDF document. */
+  data: Buffer;
+  /** 0 for "Wuolah", 1 for "StuDocu", and 2 for "Naive". */
+  method: number;
+  method_name: "Wuolah" | "StuDocu" | "Naive";
+  report: CleanReport;
+}
+
+/**
+ * Cleans a PDF document on the libuv thread pool.
+ *
+ * Rejects with a `GulagError` if the options are invalid or the document can't be cleaned.
+ */
+export function clean(data: Buffer, options?: CleanOptions): Promise<CleaningResult>;
+
+/**
+ * Cleans the PDF document at `path` and writes the result to `outPath`, on the libuv thread pool.
+ *
+ * Rejects with a `GulagError` if a file can't be read or written or the document can't be cleaned.
+ */
+export function cleanFile(path: string, outPath: string, options?: CleanOptions): Promise<CleanReport>;
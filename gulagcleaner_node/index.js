@@ -0,0 +1,2 @@
+// Loads the addon built by `npm run build`.
+module.exports = require('./gulagcleaner.node');
@@ -0,0 +1,163 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+
+use gulagcleaner_rs::report::CleanReport;
+use gulagcleaner_rs::{clean_pdf_with_report, clean_reader, CleanOptions};
+use napi::bindgen_prelude::*;
+use napi::{Env, JsObject, JsUnknown, Task};
+use napi_derive::napi;
+
+/// An error to reject the promise with, as a JS `Error` named "GulagError" with a `code`.
+#[derive(Clone)]
+struct Failure {
+    message: String,
+    code: &'static str,
+}
+
+impl From<gulagcleaner_rs::Error> for Failure {
+    fn from(e: gulagcleaner_rs::Error) -> Self {
+        Failure {
+            message: e.to_string(),
+            code: e.code(),
+        }
+    }
+}
+
+/// Stores `failure` for `reject_with` and returns the error that makes the task reject.
+fn fail<T>(slot: &mut Option<Failure>, failure: Failure) -> Result<T> {
+    let error = Error::from_reason(failure.message.clone());
+    *slot = Some(failure);
+    Err(error)
+}
+
+/// Turns the stored failure into a "GulagError", so the promise rejects like the WASM package.
+fn reject_with<T>(env: Env, failure: Option<Failure>, err: Error) -> Result<T> {
+    let Some(failure) = failure else {
+        return Err(err);
+    };
+    let mut error = env.create_error(Error::from_reason(failure.message))?;
+    error.set_named_property("name", "GulagError")?;
+    error.set_named_property("code", failure.code)?;
+    Err(Error::from(error.into_unknown()))
+}
+
+fn parse_options(
+    env: &Env,
+    options: Option<JsUnknown>,
+) -> std::result::Result<CleanOptions, Failure> {
+    match options {
+        Some(options) => env.from_js_value(options).map_err(|e| Failure {
+            message: e.reason,
+            code: "INVALID_OPTIONS",
+        }),
+        None => Ok(CleanOptions::default()),
+    }
+}
+
+pub struct CleanTask {
+    data: Buffer,
+    options: std::result::Result<CleanOptions, Failure>,
+    failure: Option<Failure>,
+}
+
+impl Task for CleanTask {
+    type Output = (Vec<u8>, CleanReport);
+    type JsValue = JsObject;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let result = match &self.options {
+            Ok(options) => clean_pdf_with_report(&self.data, options).map_err(Failure::from),
+            Err(e) => Err(e.clone()),
+        };
+        result.or_else(|failure| fail(&mut self.failure, failure))
+    }
+
+    fn resolve(&mut self, env: Env, (clean_pdf, report): Self::Output) -> Result<Self::JsValue> {
+        let mut result = env.create_object()?;
+        result.set_named_property("data", env.create_buffer_with_data(clean_pdf)?.into_raw())?;
+        result.set_named_property("method", report.method_code as u32)?;
+        result.set_named_property("method_name", report.method_name())?;
+        result.set_named_property("report", env.to_js_value(&report)?)?;
+        Ok(result)
+    }
+
+    fn reject(&mut self, env: Env, err: Error) -> Result<Self::JsValue> {
+        reject_with(env, self.failure.take(), err)
+    }
+}
+
+/// Cleans a PDF document on the libuv thread pool.
+///
+/// Resolves to a `CleaningResult`, or rejects with a `GulagError` if the options are invalid or
+/// the document can't be cleaned.
+#[napi(
+    ts_args_type = "data: Buffer, options?: CleanOptions",
+    ts_return_type = "Promise<CleaningResult>"
+)]
+pub fn clean(env: Env, data: Buffer, options: Option<JsUnknown>) -> AsyncTask<CleanTask> {
+    AsyncTask::new(CleanTask {
+        data,
+        options: parse_options(&env, options),
+        failure: None,
+    })
+}
+
+pub struct CleanFileTask {
+    path: String,
+    out_path: String,
+    options: std::result::Result<CleanOptions, Failure>,
+    failure: Option<Failure>,
+}
+
+impl CleanFileTask {
+    fn clean_file(&self, options: &CleanOptions) -> gulagcleaner_rs::Result<CleanReport> {
+        let source = BufReader::new(File::open(&self.path)?);
+        let (clean_pdf, report) = clean_reader(source, options)?;
+        fs::write(&self.out_path, clean_pdf)?;
+        Ok(report)
+    }
+}
+
+impl Task for CleanFileTask {
+    type Output = CleanReport;
+    type JsValue = JsUnknown;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let result = match &self.options {
+            Ok(options) => self.clean_file(options).map_err(Failure::from),
+            Err(e) => Err(e.clone()),
+        };
+        result.or_else(|failure| fail(&mut self.failure, failure))
+    }
+
+    fn resolve(&mut self, env: Env, report: Self::Output) -> Result<Self::JsValue> {
+        env.to_js_value(&report)
+    }
+
+    fn reject(&mut self, env: Env, err: Error) -> Result<Self::JsValue> {
+        reject_with(env, self.failure.take(), err)
+    }
+}
+
+/// Cleans the PDF document at `path` and writes the result to `outPath`, on the libuv thread
+/// pool.
+///
+/// Resolves to the `CleanReport`, or rejects with a `GulagError` if a file can't be read or
+/// written or the document can't be cleaned.
+#[napi(
+    ts_args_type = "path: string, outPath: string, options?: CleanOptions",
+    ts_return_type = "Promise<CleanReport>"
+)]
+pub fn clean_file(
+    env: Env,
+    path: String,
+    out_path: String,
+    options: Option<JsUnknown>,
+) -> AsyncTask<CleanFileTask> {
+    AsyncTask::new(CleanFileTask {
+        path,
+        out_path,
+        options: parse_options(&env, options),
+        failure: None,
+    })
+}
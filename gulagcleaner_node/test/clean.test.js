@@ -0,0 +1,31 @@
+const assert = require('assert');
+const fs = require('fs');
+const os = require('os');
+const path = require('path');
+const test = require('node:test');
+
+const { clean, cleanFile } = require('..');
+
+const STUDOCU_PATH = path.join(__dirname, '..', '..', 'gulagcleaner_rs', 'example_docs', 'studocu-example.pdf');
+
+test('clean resolves with the cleaned document and report', async () => {
+  const result = await clean(fs.readFileSync(STUDOCU_PATH));
+  assert.ok(Buffer.isBuffer(result.data));
+  assert.strictEqual(result.method_name, 'StuDocu');
+  assert.deepStrictEqual(result.report.deleted_pages, [1]);
+  assert.strictEqual(result.report.verification.status, 'Passed');
+});
+
+test('cleanFile writes the cleaned document', async () => {
+  const outPath = path.join(os.tmpdir(), `gulagcleaner-${process.pid}.pdf`);
+  const report = await cleanFile(STUDOCU_PATH, outPath, { force_naive: false });
+  assert.strictEqual(report.method_code, 1);
+  assert.ok(fs.readFileSync(outPath).subarray(0, 4).equals(Buffer.from('%PDF')));
+  fs.unlinkSync(outPath);
+});
+
+test('errors reject with a GulagError', async () => {
+  await assert.rejects(clean(Buffer.from('not a pdf')), { name: 'GulagError', code: 'PARSE_ERROR' });
+  await assert.rejects(clean(Buffer.from('not a pdf'), { force_naive: 'yes' }), { code: 'INVALID_OPTIONS' });
+  await assert.rejects(cleanFile('/does/not/exist.pdf', '/tmp/out.pdf'), { code: 'IO_ERROR' });
+});
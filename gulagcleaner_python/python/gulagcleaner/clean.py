@@ -1,50 +1,94 @@
-from ._lib import clean_pdf  # export public parts of the binary extension
+# export public parts of the binary extension
+from ._lib import (
+    clean_pdf,
+    clean,
+    analyze,
+    export_skeleton,
+    CleanOptions,
+    Limits,
+    CleanReport,
+    Analysis,
+    PageAnalysis,
+    DocumentMetadata,
+    GulagError,
+    UnsupportedDocument,
+    EncryptedDocument,
+    MetadataNotFound,
+    CleaningCancelled,
+    VerificationFailed,
+)
+from .decrypt import decrypt_pdf_bytes
 
-def clean_pdf_path(pdf_path, output_path, force_naive=False):
+def _clean_pdf(pdf_bytes, force_naive, strict, progress):
+    """
+    Calls `clean_pdf`, retrying with the file decrypted by pikepdf if it is encrypted with a
+    cipher the cleaner can't decrypt.
+    """
+    try:
+        return clean_pdf(pdf_bytes, force_naive, strict, progress)
+    except UnsupportedDocument:
+        decrypted = decrypt_pdf_bytes(pdf_bytes)
+        if decrypted is None:
+            raise
+        return clean_pdf(decrypted, force_naive, strict, progress)
+
+def clean_pdf_path(pdf_path, output_path, force_naive=False, strict=False, progress=None):
     """
     Cleans the ads from the PDF file in a given path and saves it in another path.
     Args:
         pdf_path (str): The path to the pdf file.
         output_path (str): The path to save the cleaned pdf file.
         force_naive (bool): Whether to force the naive cleaning method.
+        strict (bool): Whether to treat any cleaning warning as an error.
+        progress (callable): Called as progress(stage, page, total) before each page is
+            classified, applied and verified. Raising an exception stops the cleaning.
     Returns:
         return_msg (dict): A dictionary with the following keys:
             success (bool): Indicates whether the de-embedding process was successful.
             return_path (str): The path to the cleaned file if successful.
             method (int): The numerical code of the method used to clean the file (0-Wuolah,1-StuDocu,2-Naive).
             error (str): An error description if the process was unsuccessful.
+            error_code (str): The `code` of the error if the process was unsuccessful.
     """
     try:
         with open(pdf_path, "rb") as f:
             pdf = f.read()
-            cleaned_pdf, method = clean_pdf(pdf, force_naive)
+            cleaned_pdf, method = _clean_pdf(pdf, force_naive, strict, progress)
             with open(output_path, "wb") as f:
                 f.write(bytes(cleaned_pdf))
             return {"success": True, 
                     "return_path": output_path, 
                     "method": method,
                     "error": ""}
-    except Exception as e:
-        return {"success": False, "return_path": "", "method":"","error": str(e)}
+    except GulagError as e:
+        return {"success": False, "return_path": "", "method":"","error": str(e), "error_code": e.code}
+    except OSError as e:
+        return {"success": False, "return_path": "", "method":"","error": str(e), "error_code": "IO_ERROR"}
     
-def clean_pdf_bytes(pdf_bytes, force_naive=False):
+def clean_pdf_bytes(pdf_bytes, force_naive=False, strict=False, progress=None):
     """
     Cleans the ads from a PDF file given as bytes.
     Args:
-        pdf_bytes (bytes): The bytes of the pdf file.
+        pdf_bytes (bytes-like): The bytes of the pdf file, as bytes, bytearray or memoryview.
+            bytes is read in place; bytearray and memoryview are copied first, since
+            they could change while the file is being cleaned.
         force_naive (bool): Whether to force the naive cleaning method.
+        strict (bool): Whether to treat any cleaning warning as an error.
+        progress (callable): Called as progress(stage, page, total) before each page is
+            classified, applied and verified. Raising an exception stops the cleaning.
     Returns:
         return_msg (dict): A dictionary with the following keys:
             success (bool): Indicates whether the de-embedding process was successful.
             return_bytes (bytes): The bytes of the cleaned file if successful.
             method (int): The numerical code of the method used to clean the file (0-Wuolah,1-StuDocu,2-Naive).
             error (str): An error description if the process was unsuccessful.
+            error_code (str): The `code` of the error if the process was unsuccessful.
     """
     try:
-        cleaned_pdf, method = clean_pdf(pdf_bytes, force_naive)
+        cleaned_pdf, method = _clean_pdf(pdf_bytes, force_naive, strict, progress)
         return {"success": True, 
                 "return_bytes": bytes(cleaned_pdf), 
                 "method": method,
                 "error": ""}
-    except Exception as e:
-        return {"success": False, "return_path": "", "method":"","error": str(e)}
\ No newline at end of file
+    except GulagError as e:
+        return {"success": False, "return_path": "", "method":"","error": str(e), "error_code": e.code}
\ No newline at end of file
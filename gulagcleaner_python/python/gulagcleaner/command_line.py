@@ -1,8 +1,7 @@
 from gulagcleaner.clean import clean_pdf_path
-from gulagcleaner.decrypt import decrypt_pdf
 from gulagcleaner.metadata import extract_metadata
 from os.path import exists, isdir, join
-from os import listdir, remove
+from os import listdir
 
 def parseArgs():
     '''
@@ -12,7 +11,7 @@ def parseArgs():
     the corresponding flags.
     '''
     from sys import argv
-    targeted = ['-h', '-r', '-s', '-v','-n', argv[0]]
+    targeted = ['-h', '-r', '-s', '-v','-n', '--strict', argv[0]]
 
     return {
         'help': '-h' in argv,
@@ -20,6 +19,7 @@ def parseArgs():
         'short': '-s' in argv,
         'version': '-v' in argv,
         'force_naive': '-n' in argv,
+        'strict': '--strict' in argv,
         'files': [arg for arg in argv if arg not in targeted]
     }
 
@@ -36,6 +36,7 @@ def main():
     -r : Replace original files with their cleaned version.
     -s : Do not show metadata about cleaned files.
     -n : Force the naive cleaning method.
+    --strict : Treat any cleaning warning as an error.
     -h : Display help information.
     -v : Display the version of the program.
 
@@ -44,7 +45,7 @@ def main():
 
     # Check for the -h argument
     if arguments["help"]:
-        print("Usage: gulagcleaner [-r] [-s] [-n] [-h] [-v] <pdf_path>")
+        print("Usage: gulagcleaner [-r] [-s] [-n] [--strict] [-h] [-v] <pdf_path>")
         print("")
         print("Removes ads from PDF files.")
         print("")
@@ -55,6 +56,7 @@ def main():
         print("  -r            Replace original files with their cleaned version.")
         print("  -s            Do not show metadata about cleaned files.")
         print("  -n            Force the naive cleaning method.")
+        print("  --strict      Treat any cleaning warning as an error.")
         print("  -h            Show this help message.")
         print("  -v            Show the version of the program.")
         return
@@ -66,12 +68,13 @@ def main():
 
     # Get the pdf_path argument
     if len(arguments["files"]) == 0:
-        print('Usage: gulagcleaner [-r] [-s] [-n] [-h] [-v] <pdf_path>...')
+        print('Usage: gulagcleaner [-r] [-s] [-n] [--strict] [-h] [-v] <pdf_path>...')
         return
     
     replace = arguments["replace"]
     short = arguments["short"]
     force_naive = arguments["force_naive"]
+    strict = arguments["strict"]
     for element in arguments["files"]:
         # Check if the file exists
         if not exists(element):
@@ -92,9 +95,6 @@ def main():
         else:
             output_path = pdf_path[:-4] + "_clean.pdf"
         
-        # We decrypt the PDF file
-        pdf_path = decrypt_pdf(pdf_path)
-
         # If short mode is not active, extract metadata
         if not short:
             try:
@@ -110,8 +110,7 @@ def main():
                 print("Failed to extract metadata:", e)
 
         # Call the cleaning function
-        return_msg = clean_pdf_path(pdf_path, output_path, force_naive)
-        remove(pdf_path)
+        return_msg = clean_pdf_path(pdf_path, output_path, force_naive, strict)
         if return_msg["success"]:
             print("Cleaning successful. File saved in " + 
                   return_msg["return_path"])
@@ -1,14 +1,25 @@
-import pikepdf
+import io
 
-def decrypt_pdf(pdf_path):
+def decrypt_pdf_bytes(pdf_bytes):
     """
-    Decrypts a PDF file and returns the path to the decrypted file.
+    Decrypts a PDF file with pikepdf, for the ciphers (such as AES) the cleaner can't decrypt.
+    pikepdf is an optional dependency, installed with `pip install gulagcleaner[decrypt]`.
     Args:
-        pdf_path (str): The path to the pdf file.
+        pdf_bytes (bytes-like): The bytes of the pdf file.
     Returns:
-        intermediate_pdf_path (str): The path to the decrypted pdf file.
+        decrypted (bytes): The bytes of the decrypted file, or None if pikepdf is not
+            installed, the file is not encrypted or it can't be decrypted without a password.
     """
-    intermediate_pdf_path = pdf_path[:-4] + "_inter.pdf"
-    with pikepdf.Pdf.open(pdf_path) as pdf:
-            pdf.save(intermediate_pdf_path)
-    return intermediate_pdf_path
+    try:
+        import pikepdf
+    except ImportError:
+        return None
+    try:
+        with pikepdf.Pdf.open(io.BytesIO(bytes(pdf_bytes))) as pdf:
+            if not pdf.is_encrypted:
+                return None
+            output = io.BytesIO()
+            pdf.save(output)
+            return output.getvalue()
+    except pikepdf.PdfError:
+        return None
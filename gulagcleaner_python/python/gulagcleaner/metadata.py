@@ -1,4 +1,4 @@
-from pdfminer.high_level import extract_text
+from ._lib import extract_metadata as _extract_metadata
 
 def extract_metadata(pdf_path):
     """
@@ -16,13 +16,13 @@ def extract_metadata(pdf_path):
             "Facultad": (str) Faculty.
             "Universidad": (str) University.
     """
-    text = extract_text(pdf_path, maxpages=1)
-    metalist = list(filter(None, text.splitlines()))
+    with open(pdf_path, "rb") as f:
+        metadata = _extract_metadata(f.read())
     return {
-        "Archivo": metalist[0],
-        "Autor": metalist[1],
-        "Asignatura": metalist[2],
-        "Curso y Grado": metalist[3],
-        "Facultad": metalist[4],
-        "Universidad": metalist[5]
-    }
\ No newline at end of file
+        "Archivo": metadata.file_name,
+        "Autor": metadata.author,
+        "Asignatura": metadata.subject,
+        "Curso y Grado": metadata.course,
+        "Facultad": metadata.faculty,
+        "Universidad": metadata.university
+    }
@@ -0,0 +1,368 @@
+// The pyo3 0.20 `#[pymethods]` macro expands to impls that newer compilers flag as non-local.
+#![allow(non_local_definitions)]
+
+use gulagcleaner_rs::analyze::{Analysis, PageAnalysis};
+use gulagcleaner_rs::models::method::method_name;
+use gulagcleaner_rs::report::CleanReport;
+use gulagcleaner_rs::text::TEXT_LOSS_THRESHOLD;
+use gulagcleaner_rs::{CleanOptions, DocumentMetadata, Limits};
+use pyo3::prelude::*;
+
+/// Bounds on the resources a document may use, to protect against huge inputs and
+/// decompression bombs. A document over a limit raises `UnsupportedDocument` with the code
+/// `LIMIT_EXCEEDED`.
+#[pyclass(name = "Limits", module = "gulagcleaner")]
+#[derive(Clone)]
+pub struct PyLimits {
+    /// Maximum number of pages.
+    #[pyo3(get, set)]
+    pub max_pages: usize,
+    /// Maximum number of objects, including those stored in object streams.
+    #[pyo3(get, set)]
+    pub max_objects: usize,
+    /// Maximum number of bytes inflated from all the streams the cleaner reads.
+    #[pyo3(get, set)]
+    pub max_decompressed_bytes: usize,
+    /// Maximum number of bytes inflated from a single stream.
+    #[pyo3(get, set)]
+    pub max_stream_bytes: usize,
+}
+
+#[pymethods]
+impl PyLimits {
+    #[new]
+    #[pyo3(signature = (
+        max_pages=Limits::default().max_pages,
+        max_objects=Limits::default().max_objects,
+        max_decompressed_bytes=Limits::default().max_decompressed_bytes,
+        max_stream_bytes=Limits::default().max_stream_bytes,
+    ))]
+    fn new(
+        max_pages: usize,
+        max_objects: usize,
+        max_decompressed_bytes: usize,
+        max_stream_bytes: usize,
+    ) -> Self {
+        PyLimits {
+            max_pages,
+            max_objects,
+            max_decompressed_bytes,
+            max_stream_bytes,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Limits(max_pages={}, max_objects={}, max_decompressed_bytes={}, max_stream_bytes={})",
+            self.max_pages, self.max_objects, self.max_decompressed_bytes, self.max_stream_bytes
+        )
+    }
+}
+
+impl From<&PyLimits> for Limits {
+    fn from(limits: &PyLimits) -> Self {
+        Limits {
+            max_pages: limits.max_pages,
+            max_objects: limits.max_objects,
+            max_decompressed_bytes: limits.max_decompressed_bytes,
+            max_stream_bytes: limits.max_stream_bytes,
+        }
+    }
+}
+
+impl From<Limits> for PyLimits {
+    fn from(limits: Limits) -> Self {
+        PyLimits {
+            max_pages: limits.max_pages,
+            max_objects: limits.max_objects,
+            max_decompressed_bytes: limits.max_decompressed_bytes,
+            max_stream_bytes: limits.max_stream_bytes,
+        }
+    }
+}
+
+/// Options that control how a document is cleaned.
+#[pyclass(name = "CleanOptions", module = "gulagcleaner")]
+#[derive(Clone)]
+pub struct PyCleanOptions {
+    /// Use the naive cleaning method instead of detecting the platform.
+    #[pyo3(get, set)]
+    pub force_naive: bool,
+    /// Fraction of non-ad text that may be lost before a warning is raised.
+    #[pyo3(get, set)]
+    pub text_loss_threshold: f32,
+    /// Bounds on the size of the document, checked before its streams are inflated. The
+    /// object is shared, so changing one of its fields changes the options.
+    #[pyo3(get, set)]
+    pub limits: Py<PyLimits>,
+    /// Unix time to store as the `/ModDate` of the cleaned document, or `None` to leave the
+    /// date as it was.
+    #[pyo3(get, set)]
+    pub mod_date: Option<i64>,
+}
+
+#[pymethods]
+impl PyCleanOptions {
+    #[new]
+    #[pyo3(signature = (
+        force_naive=false,
+        text_loss_threshold=TEXT_LOSS_THRESHOLD,
+        limits=None,
+        mod_date=None,
+    ))]
+    fn new(
+        py: Python,
+        force_naive: bool,
+        text_loss_threshold: f32,
+        limits: Option<Py<PyLimits>>,
+        mod_date: Option<i64>,
+    ) -> PyResult<Self> {
+        let limits = match limits {
+            Some(limits) => limits,
+            None => Py::new(py, PyLimits::from(Limits::default()))?,
+        };
+        Ok(PyCleanOptions {
+            force_naive,
+            text_loss_threshold,
+            limits,
+            mod_date,
+        })
+    }
+
+    fn __repr__(&self, py: Python) -> String {
+        let mod_date = self.mod_date.map_or("None".to_string(), |x| x.to_string());
+        format!(
+            "CleanOptions(force_naive={}, text_loss_threshold={}, limits={}, mod_date={})",
+            py_bool(self.force_naive),
+            self.text_loss_threshold,
+            self.limits.borrow(py).__repr__(),
+            mod_date
+        )
+    }
+}
+
+impl From<&PyCleanOptions> for CleanOptions {
+    fn from(options: &PyCleanOptions) -> Self {
+        CleanOptions {
+            force_naive: options.force_naive,
+            text_loss_threshold: options.text_loss_threshold,
+            limits: Python::with_gil(|py| Limits::from(&*options.limits.borrow(py))),
+            mod_date: options.mod_date,
+        }
+    }
+}
+
+/// Summary of a cleaning run.
+#[pyclass(name = "CleanReport", module = "gulagcleaner")]
+pub struct PyCleanReport(pub CleanReport);
+
+#[pymethods]
+impl PyCleanReport {
+    /// The method used: 0 for "Wuolah", 1 for "StuDocu", and 2 for "Naive".
+    #[getter]
+    fn method_code(&self) -> u8 {
+        self.0.method_code
+    }
+
+    #[getter]
+    fn method_name(&self) -> &'static str {
+        self.0.method_name()
+    }
+
+    #[getter]
+    fn pages_before(&self) -> u32 {
+        self.0.pages_before
+    }
+
+    #[getter]
+    fn pages_after(&self) -> u32 {
+        self.0.pages_after
+    }
+
+    /// Page numbers (1-based, in the original document) that were deleted.
+    #[getter]
+    fn deleted_pages(&self) -> Vec<u32> {
+        self.0.deleted_pages.clone()
+    }
+
+    /// The verification status of the output: "Passed", "Degraded" or "Failed".
+    #[getter]
+    fn status(&self) -> String {
+        format!("{:?}", self.0.status())
+    }
+
+    /// The problems found while verifying the output.
+    #[getter]
+    fn issues(&self) -> Vec<String> {
+        let issues = &self.0.verification.issues;
+        issues.iter().map(ToString::to_string).collect()
+    }
+
+    /// The warnings raised while cleaning.
+    #[getter]
+    fn warnings(&self) -> Vec<String> {
+        self.0.warnings.iter().map(ToString::to_string).collect()
+    }
+
+    #[getter]
+    fn words_before(&self) -> usize {
+        self.0.text.words_before
+    }
+
+    #[getter]
+    fn words_after(&self) -> usize {
+        self.0.text.words_after
+    }
+
+    /// Fraction of the non-ad words that are missing from the cleaned document.
+    #[getter]
+    fn lost_ratio(&self) -> f32 {
+        self.0.text.lost_ratio()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CleanReport(method_name='{}', pages_before={}, pages_after={}, status='{:?}', warnings={})",
+            self.0.method_name(),
+            self.0.pages_before,
+            self.0.pages_after,
+            self.0.status(),
+            self.0.warnings.len()
+        )
+    }
+}
+
+/// What the cleaner found out about a single page, without modifying it.
+#[pyclass(name = "PageAnalysis", module = "gulagcleaner")]
+#[derive(Clone)]
+pub struct PyPageAnalysis {
+    /// The page number (1-based).
+    #[pyo3(get)]
+    pub page: u32,
+    /// Number of content streams drawn by the page.
+    #[pyo3(get)]
+    pub content_streams: usize,
+    /// "BannerAds", "FullPageAds", "Watermark" or "Idk".
+    #[pyo3(get)]
+    pub page_type: String,
+    /// Whether the detected method would delete the page.
+    #[pyo3(get)]
+    pub delete: bool,
+}
+
+#[pymethods]
+impl PyPageAnalysis {
+    fn __repr__(&self) -> String {
+        format!(
+            "PageAnalysis(page={}, content_streams={}, page_type='{}', delete={})",
+            self.page,
+            self.content_streams,
+            self.page_type,
+            py_bool(self.delete)
+        )
+    }
+}
+
+impl From<&PageAnalysis> for PyPageAnalysis {
+    fn from(page: &PageAnalysis) -> Self {
+        PyPageAnalysis {
+            page: page.page,
+            content_streams: page.content_streams,
+            page_type: format!("{:?}", page.page_type),
+            delete: page.delete,
+        }
+    }
+}
+
+/// The result of analyzing a document without cleaning it.
+#[pyclass(name = "Analysis", module = "gulagcleaner")]
+pub struct PyAnalysis {
+    /// The method that would be used: 0 for "Wuolah", 1 for "StuDocu", and 2 for "Naive".
+    #[pyo3(get)]
+    pub method_code: u8,
+    #[pyo3(get)]
+    pub pages: Vec<PyPageAnalysis>,
+}
+
+#[pymethods]
+impl PyAnalysis {
+    #[getter]
+    fn method_name(&self) -> &'static str {
+        method_name(self.method_code)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Analysis(method_name='{}', pages={})",
+            method_name(self.method_code),
+            self.pages.len()
+        )
+    }
+}
+
+impl From<&Analysis> for PyAnalysis {
+    fn from(analysis: &Analysis) -> Self {
+        PyAnalysis {
+            method_code: analysis.method_code,
+            pages: analysis.pages.iter().map(PyPageAnalysis::from).collect(),
+        }
+    }
+}
+
+/// The details printed on the cover page of a downloaded document.
+#[pyclass(name = "DocumentMetadata", module = "gulagcleaner")]
+pub struct PyDocumentMetadata(pub DocumentMetadata);
+
+#[pymethods]
+impl PyDocumentMetadata {
+    #[getter]
+    fn file_name(&self) -> &str {
+        &self.0.file_name
+    }
+
+    #[getter]
+    fn author(&self) -> &str {
+        &self.0.author
+    }
+
+    #[getter]
+    fn subject(&self) -> &str {
+        &self.0.subject
+    }
+
+    /// The course and degree.
+    #[getter]
+    fn course(&self) -> &str {
+        &self.0.course
+    }
+
+    #[getter]
+    fn faculty(&self) -> &str {
+        &self.0.faculty
+    }
+
+    #[getter]
+    fn university(&self) -> &str {
+        &self.0.university
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "DocumentMetadata(file_name={:?}, author={:?}, subject={:?}, course={:?}, faculty={:?}, university={:?})",
+            self.0.file_name,
+            self.0.author,
+            self.0.subject,
+            self.0.course,
+            self.0.faculty,
+            self.0.university
+        )
+    }
+}
+
+fn py_bool(value: bool) -> &'static str {
+    if value {
+        "True"
+    } else {
+        "False"
+    }
+}
@@ -1,13 +1,287 @@
+/// Python classes wrapping the options, report, analysis and metadata
+mod classes;
+
+use std::borrow::Cow;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+
+use classes::{
+    PyAnalysis, PyCleanOptions, PyCleanReport, PyDocumentMetadata, PyLimits, PyPageAnalysis,
+};
+use gulagcleaner_rs::report::CleanReport;
+use gulagcleaner_rs::{CancellationToken, CleanOptions, Error, Hooks, Progress, Stage};
+use pyo3::buffer::PyBuffer;
+use pyo3::create_exception;
+use pyo3::exceptions::PyRuntimeError;
 use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+create_exception!(
+    gulagcleaner,
+    GulagError,
+    PyRuntimeError,
+    "Base class of the errors raised while cleaning. Its `code` attribute identifies the error."
+);
+create_exception!(
+    gulagcleaner,
+    UnsupportedDocument,
+    GulagError,
+    "The input is not a PDF document that can be cleaned."
+);
+create_exception!(
+    gulagcleaner,
+    EncryptedDocument,
+    GulagError,
+    "The document is protected with a user password."
+);
+create_exception!(
+    gulagcleaner,
+    MetadataNotFound,
+    GulagError,
+    "The cover page doesn't have the expected metadata."
+);
+create_exception!(
+    gulagcleaner,
+    CleaningCancelled,
+    GulagError,
+    "The cleaning was stopped by the progress callback or a signal."
+);
+create_exception!(
+    gulagcleaner,
+    VerificationFailed,
+    GulagError,
+    "Strict mode found a warning or verification issue in the cleaned document."
+);
+
+/// Creates an exception of type `T` with a `code` attribute.
+fn coded_err<T: pyo3::PyTypeInfo>(py: Python, message: String, code: &str) -> PyErr {
+    let err = PyErr::new::<T, _>(message);
+    let _ = err.value(py).setattr("code", code);
+    err
+}
+
+/// Maps a core error onto the exception hierarchy.
+fn to_py_err(py: Python, e: Error) -> PyErr {
+    let (message, code) = (e.to_string(), e.code());
+    match e {
+        Error::Parse(_) | Error::Unsupported(_) | Error::LimitExceeded { .. } => {
+            coded_err::<UnsupportedDocument>(py, message, code)
+        }
+        Error::Encrypted(_) => coded_err::<EncryptedDocument>(py, message, code),
+        Error::MissingMetadata { .. } => coded_err::<MetadataNotFound>(py, message, code),
+        Error::Cancelled => coded_err::<CleaningCancelled>(py, message, code),
+        Error::Save(_) | Error::Io(_) | Error::InvalidOptions(_) => {
+            coded_err::<GulagError>(py, message, code)
+        }
+    }
+}
+
+/// Forwards progress to a Python callable and stops the run on Ctrl+C.
+///
+/// The cleaning runs without the GIL, so it is taken again for each update. If the callable
+/// raises, or a signal handler does, the run is cancelled and the exception is raised again
+/// once the cleaning stops.
+struct PyProgress {
+    callback: Option<PyObject>,
+    token: CancellationToken,
+    raised: Mutex<Option<PyErr>>,
+}
+
+impl Progress for PyProgress {
+    fn page(&self, stage: Stage, page: u32, total: u32) {
+        Python::with_gil(|py| {
+            let result = match &self.callback {
+                Some(callback) => callback
+                    .call1(py, (format!("{:?}", stage), page, total))
+                    .map(drop),
+                None => Ok(()),
+            };
+            if let Err(e) = result.and_then(|_| py.check_signals()) {
+                *self.raised.lock().unwrap() = Some(e);
+                self.token.cancel();
+            }
+        })
+    }
+}
+
+/// The contents of a bytes-like object.
+///
+/// `bytes` is immutable, so it is borrowed. Every other buffer is copied, since its owner
+/// could modify it while the GIL is released, even through a read-only view such as a
+/// read-only `memoryview` of a `bytearray`.
+fn buffer_bytes(data: &PyAny) -> PyResult<Cow<'_, [u8]>> {
+    if let Ok(bytes) = data.downcast_exact::<PyBytes>() {
+        return Ok(Cow::Borrowed(bytes.as_bytes()));
+    }
+    let buffer = PyBuffer::<u8>::get(data)?;
+    Ok(Cow::Owned(buffer.to_vec(data.py())?))
+}
+
+/// Runs `f` without the GIL, turning a panic into a `GulagError`.
+fn run_unlocked<T: Send>(
+    py: Python,
+    f: impl FnOnce() -> gulagcleaner_rs::Result<T> + Send,
+) -> PyResult<T> {
+    match py.allow_threads(|| panic::catch_unwind(AssertUnwindSafe(f))) {
+        Ok(result) => result.map_err(|e| to_py_err(py, e)),
+        Err(panic) => {
+            let reason = panic
+                .downcast_ref::<&str>()
+                .map(|x| x.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(coded_err::<GulagError>(
+                py,
+                format!("internal error while cleaning: {}", reason),
+                "INTERNAL_ERROR",
+            ))
+        }
+    }
+}
+
+fn run_clean(
+    py: Python,
+    data: &PyAny,
+    options: &CleanOptions,
+    strict: bool,
+    progress: Option<PyObject>,
+) -> PyResult<(Py<PyBytes>, CleanReport)> {
+    let data = buffer_bytes(data)?;
+    let progress = PyProgress {
+        callback: progress,
+        token: CancellationToken::new(),
+        raised: Mutex::new(None),
+    };
+
+    let result = run_unlocked(py, || {
+        let hooks = Hooks {
+            progress: Some(&progress),
+            cancel: Some(&progress.token),
+        };
+        gulagcleaner_rs::clean_pdf_with_hooks(&data, options, &hooks)
+    });
+    if let Some(e) = progress.raised.into_inner().unwrap() {
+        return Err(e);
+    }
+    let (clean_pdf, report) = result?;
+
+    // In strict mode, any warning or verification issue is an error.
+    if strict {
+        let problems: Vec<String> = report
+            .warnings
+            .iter()
+            .map(ToString::to_string)
+            .chain(report.verification.issues.iter().map(ToString::to_string))
+            .collect();
+        if !problems.is_empty() {
+            return Err(coded_err::<VerificationFailed>(
+                py,
+                problems.join("; "),
+                "VERIFICATION_FAILED",
+            ));
+        }
+    }
+    Ok((PyBytes::new(py, &clean_pdf).into(), report))
+}
+
+/// Cleans a PDF document given as any bytes-like object.
+///
+/// `bytes` is read in place. Other buffers, such as `bytearray` or a `memoryview`, are
+/// copied first, since Python code could change them while the document is cleaned
+/// without the GIL.
+///
+/// Returns the cleaned document and the code of the method used (0 for "Wuolah", 1 for
+/// "StuDocu", and 2 for "Naive"). Raises a subclass of `GulagError` if it can't be cleaned.
+#[pyfunction]
+#[pyo3(signature = (data, force_naive, strict=false, progress=None))]
+pub fn clean_pdf(
+    py: Python,
+    data: &PyAny,
+    force_naive: bool,
+    strict: bool,
+    progress: Option<PyObject>,
+) -> PyResult<(Py<PyBytes>, u8)> {
+    let options = CleanOptions {
+        force_naive,
+        ..Default::default()
+    };
+    let (clean_pdf, report) = run_clean(py, data, &options, strict, progress)?;
+    Ok((clean_pdf, report.method_code))
+}
+
+/// Cleans a PDF document given as any bytes-like object.
+///
+/// `bytes` is read in place, while other buffers are copied first, as in `clean_pdf`.
+///
+/// Returns the cleaned document and its `CleanReport`. Raises a subclass of `GulagError` if
+/// it can't be cleaned.
+#[pyfunction]
+#[pyo3(signature = (data, options=None, strict=false, progress=None))]
+pub fn clean(
+    py: Python,
+    data: &PyAny,
+    options: Option<PyRef<PyCleanOptions>>,
+    strict: bool,
+    progress: Option<PyObject>,
+) -> PyResult<(Py<PyBytes>, PyCleanReport)> {
+    let options = options.map(|x| CleanOptions::from(&*x)).unwrap_or_default();
+    let (clean_pdf, report) = run_clean(py, data, &options, strict, progress)?;
+    Ok((clean_pdf, PyCleanReport(report)))
+}
+
+/// Detects the cleaning method of a PDF document and classifies its pages, without cleaning it.
+#[pyfunction]
+pub fn analyze(py: Python, data: &PyAny) -> PyResult<PyAnalysis> {
+    let data = buffer_bytes(data)?;
+    let analysis = run_unlocked(py, || {
+        let doc = gulagcleaner_rs::load_document(&data)?;
+        Ok(gulagcleaner_rs::analyze_document(&doc))
+    })?;
+    Ok(PyAnalysis::from(&analysis))
+}
+
+/// Reads the metadata printed on the cover page of a PDF document, without cleaning it.
+///
+/// Raises `MetadataNotFound` if the cover page doesn't have it.
+#[pyfunction]
+pub fn extract_metadata(py: Python, data: &PyAny) -> PyResult<PyDocumentMetadata> {
+    let data = buffer_bytes(data)?;
+    let metadata = run_unlocked(py, || {
+        let doc = gulagcleaner_rs::load_document(&data)?;
+        gulagcleaner_rs::extract_metadata(&doc)
+    })?;
+    Ok(PyDocumentMetadata(metadata))
+}
 
+/// Builds an anonymized copy of a PDF document to attach to a bug report.
+///
+/// The copy keeps the page tree, the content stream operators and the image sizes, which
+/// reproduce how the document is detected, but has its text and pixels blanked out.
 #[pyfunction]
-pub fn clean_pdf(data: Vec<u8>, force_naive: bool) -> PyResult<(Vec<u8>, u8)> {
-    let (clean_pdf, method_code) = gulagcleaner_rs::clean_pdf(data, force_naive);
-    Ok((clean_pdf, method_code))
+pub fn export_skeleton(py: Python, data: &PyAny) -> PyResult<Py<PyBytes>> {
+    let data = buffer_bytes(data)?;
+    let skeleton = run_unlocked(py, || gulagcleaner_rs::export_skeleton_pdf(&data))?;
+    Ok(PyBytes::new(py, &skeleton).into())
 }
 
 #[pymodule]
-fn _lib(_py: Python, m: &PyModule) -> PyResult<()> {
+fn _lib(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(clean_pdf, m)?)?;
+    m.add_function(wrap_pyfunction!(clean, m)?)?;
+    m.add_function(wrap_pyfunction!(analyze, m)?)?;
+    m.add_function(wrap_pyfunction!(extract_metadata, m)?)?;
+    m.add_function(wrap_pyfunction!(export_skeleton, m)?)?;
+    m.add_class::<PyCleanOptions>()?;
+    m.add_class::<PyLimits>()?;
+    m.add_class::<PyCleanReport>()?;
+    m.add_class::<PyAnalysis>()?;
+    m.add_class::<PyPageAnalysis>()?;
+    m.add_class::<PyDocumentMetadata>()?;
+    m.add("GulagError", py.get_type::<GulagError>())?;
+    m.add("UnsupportedDocument", py.get_type::<UnsupportedDocument>())?;
+    m.add("EncryptedDocument", py.get_type::<EncryptedDocument>())?;
+    m.add("MetadataNotFound", py.get_type::<MetadataNotFound>())?;
+    m.add("CleaningCancelled", py.get_type::<CleaningCancelled>())?;
+    m.add("VerificationFailed", py.get_type::<VerificationFailed>())?;
     Ok(())
 }
@@ -0,0 +1,47 @@
+import os
+import unittest
+
+from gulagcleaner.clean import CleanOptions, GulagError, Limits, UnsupportedDocument, clean
+
+STUDOCU_PATH = os.path.join(
+    os.path.dirname(__file__), "..", "..", "gulagcleaner_rs", "example_docs", "studocu-example.pdf"
+)
+
+
+def read_studocu():
+    with open(STUDOCU_PATH, "rb") as f:
+        return f.read()
+
+
+class CleanOptionsTest(unittest.TestCase):
+    def test_tight_limit_raises_unsupported_document(self):
+        options = CleanOptions(limits=Limits(max_pages=1))
+        with self.assertRaises(UnsupportedDocument) as raised:
+            clean(read_studocu(), options)
+        self.assertEqual(raised.exception.code, "LIMIT_EXCEEDED")
+
+        # The limits are shared with the options, so they can be changed in place
+        options = CleanOptions()
+        options.limits.max_stream_bytes = 16
+        with self.assertRaises(UnsupportedDocument) as raised:
+            clean(read_studocu(), options)
+        self.assertEqual(raised.exception.code, "LIMIT_EXCEEDED")
+
+    def test_mod_date_is_stored(self):
+        options = CleanOptions(mod_date=1_700_000_000)
+        self.assertEqual(options.mod_date, 1_700_000_000)
+        cleaned, _ = clean(read_studocu(), options)
+        self.assertIn(b"D:20231114", cleaned)
+
+        options.mod_date = None
+        cleaned, _ = clean(read_studocu(), options)
+        self.assertEqual(cleaned, clean(read_studocu())[0])
+
+        options.mod_date = 10**15
+        with self.assertRaises(GulagError) as raised:
+            clean(read_studocu(), options)
+        self.assertEqual(raised.exception.code, "INVALID_OPTIONS")
+
+
+if __name__ == "__main__":
+    unittest.main()
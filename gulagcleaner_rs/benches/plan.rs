@@ -0,0 +1,58 @@
+//! Times detection and planning on large synthetic documents.
+//!
+//! Run with `cargo bench --features fixtures`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use gulagcleaner_rs::fixtures;
+use gulagcleaner_rs::lopdf::Document;
+use gulagcleaner_rs::models::page_type::PageType;
+use gulagcleaner_rs::{analyze_document, plan_document, CleanOptions};
+
+/// Number of pages of each document.
+const PAGES: u32 = 500;
+
+/// Number of times each function is timed.
+const ITERATIONS: usize = 20;
+
+/// Prints the median time `f` takes over [`ITERATIONS`] runs.
+fn bench(name: &str, mut f: impl FnMut()) {
+    let mut times: Vec<Duration> = (0..ITERATIONS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    println!("{:<24} {:>10.2?}", name, times[ITERATIONS / 2]);
+}
+
+fn main() {
+    let naive_pages: Vec<PageType> = [
+        PageType::BannerAds,
+        PageType::Watermark,
+        PageType::FullPageAds,
+        PageType::Idk,
+    ]
+    .into_iter()
+    .cycle()
+    .take(PAGES as usize)
+    .collect();
+    let docs: [(&str, Document); 3] = [
+        ("wuolah", fixtures::wuolah(PAGES)),
+        ("studocu", fixtures::studocu(PAGES)),
+        ("naive", fixtures::naive(&naive_pages)),
+    ];
+
+    let options = CleanOptions::default();
+    for (name, doc) in &docs {
+        bench(&format!("plan/{}", name), || {
+            black_box(plan_document(doc, &options));
+        });
+        bench(&format!("analyze/{}", name), || {
+            black_box(analyze_document(doc));
+        });
+    }
+}
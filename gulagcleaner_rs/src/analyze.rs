@@ -0,0 +1,66 @@
+use lopdf::Document;
+use serde::{Deserialize, Serialize};
+
+use crate::clean::{match_method, Cleaner};
+use crate::error::Result;
+use crate::index::PageIndex;
+use crate::models::page_type::PageType;
+use crate::progress::{uncancellable, Hooks};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// What the cleaner found out about a single page, without modifying it.
+pub struct PageAnalysis {
+    /// The page number (1-based).
+    pub page: u32,
+    /// Number of content streams drawn by the page.
+    pub content_streams: usize,
+    /// The type of the page according to the images it contains.
+    pub page_type: PageType,
+    /// Whether the detected method would delete the page.
+    pub delete: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// The result of analyzing a document without cleaning it.
+pub struct Analysis {
+    /// The method that would be used: 0 for "Wuolah", 1 for "StuDocu", and 2 for "Naive".
+    pub method_code: u8,
+    pub pages: Vec<PageAnalysis>,
+}
+
+/// Detects the cleaning method of a document and classifies its pages, without modifying it.
+///
+/// # Arguments
+///
+/// * `doc` - A reference to the `Document` to analyze.
+pub fn analyze_document(doc: &Document) -> Analysis {
+    uncancellable(analyze_document_with_hooks(doc, &Hooks::default()))
+}
+
+/// Analyzes a document like [`analyze_document`], reporting progress and checking for
+/// cancellation between pages.
+///
+/// # Returns
+///
+/// The `Analysis`, or `Error::Cancelled` if the run was cancelled.
+pub fn analyze_document_with_hooks(doc: &Document, hooks: &Hooks) -> Result<Analysis> {
+    let index = PageIndex::new(doc);
+    let plan = match_method(&index, false).plan(&index, hooks)?;
+    let deleted_pages = plan.deleted_pages(index.len() as u32);
+
+    let pages = index
+        .pages()
+        .iter()
+        .map(|page| PageAnalysis {
+            page: page.number,
+            content_streams: page.contents.len(),
+            page_type: page.page_type().unwrap_or_default(),
+            delete: deleted_pages.contains(&page.number),
+        })
+        .collect();
+
+    Ok(Analysis {
+        method_code: plan.method_code,
+        pages,
+    })
+}
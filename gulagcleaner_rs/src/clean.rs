@@ -1,11 +1,66 @@
+use crate::error::{Error, Result};
+use crate::index::PageIndex;
+use crate::limits::Limits;
 use crate::models::method::Method;
+use crate::plan::CleanPlan;
+use crate::progress::{uncancellable, Hooks};
+use crate::report::{CleanReport, Warning};
+use crate::save::{mod_date_time, save_document};
+use crate::text::{compare_text, extract_page_text, TEXT_LOSS_THRESHOLD};
+use crate::verify::{verify_document_with_hooks, verify_pdf_with_hooks};
 
-use lopdf::Document;
+use lopdf::encryption::DecryptionError;
+use lopdf::{Document, Object};
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::io::{Read, Write};
 
 /// Trait implemented by the different PDF methods
 pub trait Cleaner {
-    fn clean(&mut self, doc: &mut Document) -> (Vec<u32>, u8);
+    /// Decides what to change in the document, from the index of its pages.
+    ///
+    /// Fails only if the run is cancelled through `hooks`.
+    fn plan(&self, index: &PageIndex, hooks: &Hooks) -> Result<CleanPlan>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+/// Options that control how a document is cleaned.
+pub struct CleanOptions {
+    /// Use the naive cleaning method instead of detecting the platform.
+    pub force_naive: bool,
+    /// Fraction of non-ad text that may be lost before a `Warning::TextLoss` is raised.
+    pub text_loss_threshold: f32,
+    /// Bounds on the size of the document, checked before its streams are inflated.
+    pub limits: Limits,
+    /// Unix time to store as the `/ModDate` of the cleaned document. When `None` the date is
+    /// left as it was, so the output only depends on the input.
+    pub mod_date: Option<i64>,
+}
+
+impl CleanOptions {
+    /// Checks that the options can be applied, before any work is done.
+    ///
+    /// # Returns
+    ///
+    /// `Error::InvalidOptions` if `mod_date` is outside the range of dates that can be stored.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(timestamp) = self.mod_date {
+            mod_date_time(timestamp)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for CleanOptions {
+    fn default() -> Self {
+        CleanOptions {
+            force_naive: false,
+            text_loss_threshold: TEXT_LOSS_THRESHOLD,
+            limits: Limits::default(),
+            mod_date: None,
+        }
+    }
 }
 
 /// Cleans a PDF document by modifying its pages and removing unnecessary content.
@@ -20,30 +75,343 @@ pub trait Cleaner {
 /// A tuple containing the cleaned PDF document data as a vector of bytes and a method code.
 ///
 /// The method code indicates the cleaning method used: 0 for "Wuolah", 1 for "StuDocu", and 2 for "Naive".
+///
+/// # Panics
+///
+/// Panics if the data is not a PDF document. Use [`clean_pdf_with_report`] to handle errors.
+#[deprecated(
+    since = "0.14.1",
+    note = "panics on documents it can't clean, use `clean_pdf_with_report` instead"
+)]
 pub fn clean_pdf(data: Vec<u8>, force_naive: bool) -> (Vec<u8>, u8) {
+    let options = CleanOptions {
+        force_naive,
+        ..Default::default()
+    };
+    let (return_stream, report) = clean_pdf_with_report(&data, &options).unwrap();
+    (return_stream, report.method_code)
+}
+
+/// Parses PDF document data, for the functions that work on a `Document`.
+///
+/// Documents encrypted without a user password (the usual copy protection) are decrypted.
+///
+/// # Returns
+///
+/// The `Document`, `Error::Encrypted` if it needs a password, `Error::Unsupported` if it
+/// has no pages, or `Error::LimitExceeded` if it goes over the default `Limits`.
+pub fn load_document(data: &[u8]) -> Result<Document> {
+    load_document_with_limits(data, &Limits::default())
+}
+
+/// Parses PDF document data like [`load_document`], checking it against `limits`.
+///
+/// Object streams are measured before they are inflated, so a decompression bomb is
+/// rejected without being expanded.
+pub fn load_document_with_limits(data: &[u8], limits: &Limits) -> Result<Document> {
+    let (doc, budget) = limits.load(data)?;
+    let doc = prepare_document(doc)?;
+    limits.check_loaded(&doc, budget)?;
+    Ok(doc)
+}
+
+fn prepare_document(mut doc: Document) -> Result<Document> {
+    if doc.is_encrypted() {
+        doc.decrypt("").map_err(|e| match e {
+            lopdf::Error::Decryption(DecryptionError::UnsupportedEncryption) => {
+                Error::Unsupported(format!(
+                    "it is encrypted with {}, which can't be decrypted",
+                    cipher_name(&doc)
+                ))
+            }
+            e => Error::Encrypted(e),
+        })?;
+    }
+    if doc.get_pages().is_empty() {
+        return Err(Error::Unsupported("the document has no pages".to_string()));
+    }
+    Ok(doc)
+}
 
+/// Names the cipher of an encrypted document, from the `/V` and `/R` of its encryption
+/// dictionary and the method of its standard crypt filter.
+fn cipher_name(doc: &Document) -> String {
+    let Ok(encrypt) = doc
+        .trailer
+        .get(b"Encrypt")
+        .and_then(|x| doc.dereference(x))
+        .and_then(|(_, x)| x.as_dict())
+    else {
+        return "an unknown cipher".to_string();
+    };
+    let number = |key: &[u8]| encrypt.get(key).and_then(Object::as_i64).ok();
+    let method = encrypt
+        .get(b"CF")
+        .and_then(Object::as_dict)
+        .and_then(|x| {
+            x.get(
+                encrypt
+                    .get(b"StmF")
+                    .and_then(Object::as_name)
+                    .unwrap_or(b"StdCF"),
+            )
+        })
+        .and_then(Object::as_dict)
+        .and_then(|x| x.get(b"CFM"))
+        .and_then(Object::as_name_str);
+    let cipher = match (number(b"V"), method) {
+        (_, Ok("AESV2")) => "AES-128",
+        (_, Ok("AESV3")) | (Some(5), _) => "AES-256",
+        (Some(4), Ok("V2")) => "RC4 with crypt filters",
+        _ => "an unsupported cipher",
+    };
+    match (number(b"V"), number(b"R")) {
+        (Some(v), Some(r)) => format!("{} (V {}, R {})", cipher, v, r),
+        _ => cipher.to_string(),
+    }
+}
+
+/// Cleans a PDF document and verifies the result.
+///
+/// Works like [`clean_pdf`], but the cleaned output is parsed back and checked before it
+/// is returned. Callers should inspect [`CleanReport::status`] before writing the data.
+///
+/// # Returns
+///
+/// A tuple containing the cleaned PDF document data and a `CleanReport` describing the run.
+pub fn clean_pdf_with_report(
+    data: &[u8],
+    options: &CleanOptions,
+) -> Result<(Vec<u8>, CleanReport)> {
+    clean_pdf_with_hooks(data, options, &Hooks::default())
+}
+
+/// Cleans a PDF document and verifies the result, reporting progress and checking for
+/// cancellation between pages.
+///
+/// # Returns
+///
+/// A tuple containing the cleaned PDF document data and a `CleanReport` describing the run,
+/// or `Error::Cancelled` if the run was cancelled.
+pub fn clean_pdf_with_hooks(
+    data: &[u8],
+    options: &CleanOptions,
+    hooks: &Hooks,
+) -> Result<(Vec<u8>, CleanReport)> {
+    options.validate()?;
     //Load the PDF into a Document
-    let mut doc = Document::load_mem(&data).unwrap();
+    let mut doc = load_document_with_limits(data, &options.limits)?;
+
+    let mut return_stream = Vec::new();
+    let report = clean_into(&mut doc, &mut return_stream, options, hooks)?;
+    Ok((return_stream, report))
+}
+
+/// Reads a PDF document from `source` and cleans it.
+///
+/// # Returns
+///
+/// A tuple containing the cleaned PDF document data and a `CleanReport` describing the run.
+pub fn clean_reader<R: Read>(
+    mut source: R,
+    options: &CleanOptions,
+) -> Result<(Vec<u8>, CleanReport)> {
+    options.validate()?;
+    let mut data = Vec::new();
+    source.read_to_end(&mut data)?;
+    let mut doc = load_document_with_limits(&data, &options.limits)?;
+
+    let mut return_stream = Vec::new();
+    let report = clean_to_writer(&mut doc, &mut return_stream, options)?;
+    Ok((return_stream, report))
+}
+
+/// Cleans a document and writes the result to `target`.
+///
+/// The output is parsed back and verified before it is written, so a document that fails
+/// to re-parse is reported in the `CleanReport` instead of being written silently.
+pub fn clean_to_writer<W: Write>(
+    doc: &mut Document,
+    target: &mut W,
+    options: &CleanOptions,
+) -> Result<CleanReport> {
+    options.validate()?;
+    options.limits.check(doc)?;
+    clean_into(doc, target, options, &Hooks::default())
+}
+
+fn clean_into<W: Write>(
+    doc: &mut Document,
+    target: &mut W,
+    options: &CleanOptions,
+    hooks: &Hooks,
+) -> Result<CleanReport> {
+    let plan = make_plan(doc, options, hooks)?;
+    let mut report = apply_unverified(doc, &plan, options, hooks)?;
+
+    //Save the document.
+    options.limits.check_counts(doc)?;
+    let return_stream = save_document(doc, options)?;
+
+    report.verification = verify_pdf_with_hooks(&return_stream, report.pages_after, hooks)?;
+    target.write_all(&return_stream)?;
+    Ok(report)
+}
+
+/// Cleans a document in place.
+///
+/// This is the entry point for callers that already hold a `lopdf::Document`. The document
+/// is verified in memory; it is not serialised.
+///
+/// # Arguments
+///
+/// * `doc` - The document to clean.
+/// * `options` - The options that control the cleaning.
+///
+/// # Returns
+///
+/// A `CleanReport` describing the run.
+pub fn clean_document(doc: &mut Document, options: &CleanOptions) -> Result<CleanReport> {
+    clean_document_with_hooks(doc, options, &Hooks::default())
+}
+
+/// Cleans a document in place, reporting progress and checking for cancellation between pages.
+///
+/// A cancelled run returns `Error::Cancelled` and may leave the document partially cleaned.
+pub fn clean_document_with_hooks(
+    doc: &mut Document,
+    options: &CleanOptions,
+    hooks: &Hooks,
+) -> Result<CleanReport> {
+    options.limits.check(doc)?;
+    let plan = make_plan(doc, options, hooks)?;
+    apply_and_verify(doc, &plan, options, hooks)
+}
+
+/// Decides how to clean a document, without modifying it.
+///
+/// The returned `CleanPlan` can be inspected, edited or stored, and then executed with
+/// [`apply_plan`].
+///
+/// The document is not checked against `options.limits`; use [`plan_document_with_hooks`]
+/// for documents that were not loaded with [`load_document_with_limits`].
+pub fn plan_document(doc: &Document, options: &CleanOptions) -> CleanPlan {
+    uncancellable(make_plan(doc, options, &Hooks::default()))
+}
+
+/// Decides how to clean a document, reporting progress and checking for cancellation
+/// between pages.
+pub fn plan_document_with_hooks(
+    doc: &Document,
+    options: &CleanOptions,
+    hooks: &Hooks,
+) -> Result<CleanPlan> {
+    options.limits.check(doc)?;
+    make_plan(doc, options, hooks)
+}
 
+pub(crate) fn make_plan(
+    doc: &Document,
+    options: &CleanOptions,
+    hooks: &Hooks,
+) -> Result<CleanPlan> {
     //We first need to determine what method we're using, either "Wuolah", "StuDocu" or "Wuolah naive".
     // We keep it like this to allow for future methods if needed.
 
-    //Each method should mark pages for deletion in to_delete and modify the contents of the pages.
+    //Each method should mark pages for deletion and plan the changes to the contents of the pages.
+    let index = PageIndex::new(doc);
+    match_method(&index, options.force_naive).plan(&index, hooks)
+}
+
+/// Applies a `CleanPlan` to a document and verifies the result in memory.
+///
+/// # Returns
+///
+/// A `CleanReport` describing the run.
+pub fn apply_plan(
+    doc: &mut Document,
+    plan: &CleanPlan,
+    options: &CleanOptions,
+) -> Result<CleanReport> {
+    apply_plan_with_hooks(doc, plan, options, &Hooks::default())
+}
+
+/// Applies a `CleanPlan` to a document and verifies the result in memory, reporting progress
+/// and checking for cancellation between pages.
+pub fn apply_plan_with_hooks(
+    doc: &mut Document,
+    plan: &CleanPlan,
+    options: &CleanOptions,
+    hooks: &Hooks,
+) -> Result<CleanReport> {
+    options.limits.check(doc)?;
+    apply_and_verify(doc, plan, options, hooks)
+}
+
+fn apply_and_verify(
+    doc: &mut Document,
+    plan: &CleanPlan,
+    options: &CleanOptions,
+    hooks: &Hooks,
+) -> Result<CleanReport> {
+    let mut report = apply_unverified(doc, plan, options, hooks)?;
+    report.verification = verify_document_with_hooks(doc, report.pages_after, hooks)?;
+    Ok(report)
+}
+
+fn apply_unverified(
+    doc: &mut Document,
+    plan: &CleanPlan,
+    options: &CleanOptions,
+    hooks: &Hooks,
+) -> Result<CleanReport> {
+    let pages = doc.get_pages();
+    let pages_before = pages.len() as u32;
+
+    //Keep the original text around to check that cleaning didn't drop any real content.
+    let text_before = pages
+        .values()
+        .map(|page| {
+            hooks.check()?;
+            Ok(extract_page_text(doc, *page))
+        })
+        .collect::<Result<Vec<Vec<String>>>>()?;
 
-    let (to_delete, method_code) = match_method(&doc, force_naive).clean(&mut doc);
+    let mut warnings = plan.warnings.clone();
+    warnings.extend(plan.apply_with_hooks(doc, hooks)?);
+    let deleted_pages = plan.deleted_pages(pages_before);
+
+    let kept_text: Vec<Vec<String>> = text_before
+        .into_iter()
+        .zip(1..)
+        .filter(|(_, page)| !deleted_pages.contains(page))
+        .map(|(text, _)| text)
+        .collect();
+    let text_after = doc
+        .page_iter()
+        .map(|page| {
+            hooks.check()?;
+            Ok(extract_page_text(doc, page))
+        })
+        .collect::<Result<Vec<Vec<String>>>>()?;
+    let text = compare_text(&kept_text, &text_after);
 
-    //Delete the pages that we've marked for deletion.
-    for (offset, page) in to_delete.into_iter().enumerate() {
-        doc.delete_pages(&[page - offset as u32]);
+    if text.lost_ratio() > options.text_loss_threshold {
+        warnings.push(Warning::TextLoss {
+            lost_ratio: text.lost_ratio(),
+            threshold: options.text_loss_threshold,
+        });
     }
-    //Save the document.
-    let mut return_stream = Vec::new();
-    doc.save_to(&mut return_stream).unwrap();
 
-    // Should we still return the method_code now that we are going multi-language? I will leave it not returned for now.
-    //return_stream.push(method_code);
-    (return_stream, method_code)
-    //doc.save_to("test.pdf").unwrap();
+    Ok(CleanReport {
+        method_code: plan.method_code,
+        pages_before,
+        pages_after: pages_before - deleted_pages.len() as u32,
+        deleted_pages,
+        text,
+        warnings,
+        ..Default::default()
+    })
 }
 
 /// Creates a new `Method` instance based on the provided `Document` and `force_naive` flag.
@@ -56,27 +424,22 @@ pub fn clean_pdf(data: Vec<u8>, force_naive: bool) -> (Vec<u8>, u8) {
 /// # Returns
 ///
 /// A `Method` instance representing the chosen method based on the provided `Document` and `force_naive` flag.
-
-fn match_method(doc: &Document, force_naive: bool) -> Method {
+pub(crate) fn match_method(index: &PageIndex, force_naive: bool) -> Method {
     //0 for auto, 1 for wuolah, 2 for studocu 3 for wuolah naive
     if force_naive {
         return Method::Naive;
     }
 
-    let pages = doc.get_pages();
+    let pages = index.pages();
     let content_list: Vec<Vec<(u32, u16)>> = pages
         .iter()
-        .map(|x| doc.get_page_contents(*x.1))
+        .map(|x| x.contents.clone())
         .filter(|x| x.len() > 1)
         .collect();
     let to_delete: Vec<u32> = pages
         .iter()
-        .filter(|x| {
-            let contents = doc.get_page_contents(*x.1);
-
-            contents.len() < 4
-        })
-        .map(|x| *x.0)
+        .filter(|x| x.contents.len() < 4)
+        .map(|x| x.number)
         .collect();
 
     if content_list
@@ -91,7 +454,7 @@ fn match_method(doc: &Document, force_naive: bool) -> Method {
     }
     let long_content_list: Vec<Vec<(u32, u16)>> = pages
         .iter()
-        .map(|x| doc.get_page_contents(*x.1))
+        .map(|x| x.contents.clone())
         .filter(|x| x.len() > 3)
         .collect();
 
@@ -0,0 +1,88 @@
+use crate::limits::Limit;
+
+use std::fmt;
+
+/// Errors that stop a document from being cleaned.
+#[derive(Debug)]
+pub enum Error {
+    /// The input could not be parsed as a PDF document.
+    Parse(lopdf::Error),
+    /// The document is encrypted with a password.
+    Encrypted(lopdf::Error),
+    /// The document parsed, but can't be cleaned.
+    Unsupported(String),
+    /// The cleaned document could not be written.
+    Save(std::io::Error),
+    /// Reading the input or writing the output failed.
+    Io(std::io::Error),
+    /// The cover page doesn't have the expected metadata lines.
+    MissingMetadata { found: usize },
+    /// The run was stopped through its `CancellationToken`.
+    Cancelled,
+    /// The document goes over one of the configured `Limits`.
+    LimitExceeded { limit: Limit, max: usize },
+    /// One of the `CleanOptions` can't be applied.
+    InvalidOptions(String),
+}
+
+impl Error {
+    /// A stable code identifying the kind of error, for bindings that can't match on the enum.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Parse(_) => "PARSE_ERROR",
+            Error::Encrypted(_) => "ENCRYPTED_DOCUMENT",
+            Error::Unsupported(_) => "UNSUPPORTED_DOCUMENT",
+            Error::Save(_) => "SAVE_ERROR",
+            Error::Io(_) => "IO_ERROR",
+            Error::MissingMetadata { .. } => "METADATA_NOT_FOUND",
+            Error::Cancelled => "CANCELLED",
+            Error::LimitExceeded { .. } => "LIMIT_EXCEEDED",
+            Error::InvalidOptions(_) => "INVALID_OPTIONS",
+        }
+    }
+}
+
+/// Result type used throughout the crate.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(e) => write!(f, "could not parse the document: {}", e),
+            Error::Encrypted(e) => write!(f, "the document is password protected: {}", e),
+            Error::Unsupported(reason) => write!(f, "the document is not supported: {}", reason),
+            Error::Save(e) => write!(f, "could not save the cleaned document: {}", e),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::MissingMetadata { found } => write!(
+                f,
+                "the cover page has {} lines of text, expected at least 6",
+                found
+            ),
+            Error::Cancelled => write!(f, "the cleaning was cancelled"),
+            Error::LimitExceeded { limit, max } => {
+                write!(f, "the document exceeds the limit of {} {}", max, limit)
+            }
+            Error::InvalidOptions(reason) => write!(f, "the options are not valid: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(e) | Error::Encrypted(e) => Some(e),
+            Error::Save(e) | Error::Io(e) => Some(e),
+            Error::Unsupported(_)
+            | Error::MissingMetadata { .. }
+            | Error::Cancelled
+            | Error::LimitExceeded { .. }
+            | Error::InvalidOptions(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+
+use crate::models::page_type::{
+    PageType, FULL_PAGE_DIMS, HORIZONTAL_BANNER_DIMS, LOGO_DIMS, VERTICAL_BANNER_DIMS,
+};
+
+/// Size of every synthetic page, A4 in points.
+const PAGE_SIZE: (i64, i64) = (595, 842);
+
+/// Size of the image on the `Idk` pages of [`naive`], which matches no ad.
+const PHOTO_DIMS: (i64, i64) = (300, 400);
+
+/// The text drawn on the `n`th notes page (1-based, counting only notes pages) of a fixture.
+pub fn notes_text(n: u32) -> String {
+    format!("Apuntes de la pagina {}", n)
+}
+
+/// A Wuolah download as served before June 2024.
+///
+/// The document starts and ends with a full-page ad. Each of the `notes_pages` pages in
+/// between has 9 content streams:
+///
+/// | Index | Stream                                   |
+/// |-------|------------------------------------------|
+/// | 0     | The banner, drawn over the page          |
+/// | 1-2   | `q` and the `cm` that shrinks the notes  |
+/// | 3-4   | `q` and `Q` streams shared by every page |
+/// | 5     | The notes                                |
+/// | 6-7   | `Q` and a closing stream                 |
+/// | 8     | The logo in the footer                   |
+///
+/// Cleaning keeps streams 1 to 7, deletes the full-page ads and removes the link annotations.
+/// `notes_pages` must be at least 2, since the method is detected by comparing two pages.
+pub fn wuolah(notes_pages: u32) -> Document {
+    wuolah_document(notes_pages, false)
+}
+
+/// A Wuolah download as served since June 2024.
+///
+/// It is laid out like [`wuolah`], but the footer stream is also shared by every page, so
+/// consecutive pages have three content streams in common instead of two.
+pub fn wuolah_010624(notes_pages: u32) -> Document {
+    wuolah_document(notes_pages, true)
+}
+
+fn wuolah_document(notes_pages: u32, shared_footer: bool) -> Document {
+    let mut builder = Builder::new();
+    let (width, height) = PAGE_SIZE;
+
+    builder.full_page_ad();
+    let open = builder.stream("q");
+    let close = builder.stream("Q");
+    let shared = builder.stream(&logo_footer());
+    let banner = builder.image(HORIZONTAL_BANNER_DIMS[0]);
+    let logo = builder.image(LOGO_DIMS[0]);
+    for n in 1..=notes_pages {
+        let footer = if shared_footer {
+            shared
+        } else {
+            builder.stream(&logo_footer())
+        };
+        let contents = vec![
+            builder.stream(&format!(
+                "q {} 0 0 70 0 {} cm /Banner Do Q",
+                width,
+                height - 70
+            )),
+            builder.stream("q"),
+            builder.stream("0.85 0 0 0.85 45 60 cm"),
+            open,
+            close,
+            builder.stream(&notes(n)),
+            builder.stream("Q"),
+            builder.stream("0 g"),
+            footer,
+        ];
+        builder.page(contents, &[("Banner", banner), ("Logo", logo)]);
+    }
+    builder.full_page_ad();
+    builder.finish()
+}
+
+/// A StuDocu download.
+///
+/// Every page has 3 content streams: a header, the notes and a footer. The first page is the
+/// StuDocu cover, followed by `notes_pages` pages of notes. Cleaning deletes the cover and
+/// keeps only the middle stream of the other pages.
+pub fn studocu(notes_pages: u32) -> Document {
+    let mut builder = Builder::new();
+    for n in 0..=notes_pages {
+        let body = if n == 0 {
+            text(16, 72, 700, "Studocu")
+        } else {
+            notes(n)
+        };
+        let contents = vec![
+            builder.stream(&text(8, 72, 820, "Descargado por Alguien")),
+            builder.stream(&body),
+            builder.stream(&text(8, 72, 20, "Studocu no esta patrocinado")),
+        ];
+        builder.page(contents, &[]);
+    }
+    builder.finish()
+}
+
+/// A document only the Naive method can clean, with one page of each of `pages`.
+///
+/// The pages draw images with the dimensions `PageType::get_page_type` looks for:
+///
+/// * `FullPageAds` - a full-page image.
+/// * `BannerAds` - a horizontal and a vertical banner, a logo and the notes.
+/// * `Watermark` - a logo and the notes.
+/// * `Idk` - a photo of a size no ad has, and the notes.
+///
+/// Every page has a single content stream.
+pub fn naive(pages: &[PageType]) -> Document {
+    let mut builder = Builder::new();
+    let (width, height) = PAGE_SIZE;
+    let mut n = 0;
+    for page_type in pages {
+        if *page_type == PageType::FullPageAds {
+            builder.full_page_ad();
+            continue;
+        }
+
+        n += 1;
+        let mut content = String::new();
+        let mut xobjects = Vec::new();
+        if *page_type == PageType::BannerAds {
+            let banner = builder.image(HORIZONTAL_BANNER_DIMS[1]);
+            let side = builder.image(VERTICAL_BANNER_DIMS[1]);
+            content += &format!("q {} 0 0 70 0 {} cm /Banner Do Q\n", width, height - 70);
+            content += &format!("q 60 0 0 {} 0 0 cm /Side Do Q\n", height);
+            xobjects.extend([("Banner", banner), ("Side", side)]);
+        }
+        if *page_type == PageType::Idk {
+            let photo = builder.image(PHOTO_DIMS);
+            content += "q 200 0 0 150 72 500 cm /Photo Do Q\n";
+            xobjects.push(("Photo", photo));
+        }
+        if matches!(page_type, PageType::BannerAds | PageType::Watermark) {
+            let logo = builder.image(LOGO_DIMS[1]);
+            content += &logo_footer();
+            content += "\n";
+            xobjects.push(("Logo", logo));
+        }
+        content += &notes(n);
+
+        let contents = vec![builder.stream(&content)];
+        builder.page(contents, &xobjects);
+    }
+    builder.finish()
+}
+
+/// A document of `notes_pages` pages with only text, and no `/XObject` resources, like one
+/// typed from scratch. The Naive method must leave every page as it is.
+pub fn text_only(notes_pages: u32) -> Document {
+    let mut builder = Builder::new();
+    for n in 1..=notes_pages {
+        let contents = vec![builder.stream(&notes(n))];
+        builder.page(contents, &[]);
+    }
+    builder.finish()
+}
+
+/// Saves a fixture to memory.
+pub fn to_bytes(doc: &mut Document) -> Vec<u8> {
+    let mut data = Vec::new();
+    doc.save_to(&mut data)
+        .expect("writing to memory does not fail");
+    data
+}
+
+fn notes(n: u32) -> String {
+    text(12, 72, 700, &notes_text(n))
+}
+
+fn text(size: i64, x: i64, y: i64, text: &str) -> String {
+    format!("BT /F1 {} Tf {} {} Td ({}) Tj ET", size, x, y, text)
+}
+
+fn logo_footer() -> String {
+    "q 120 0 0 22 237 10 cm /Logo Do Q".to_string()
+}
+
+/// Builds a document page by page, sharing the font and the images between pages.
+struct Builder {
+    doc: Document,
+    pages_id: ObjectId,
+    font_id: ObjectId,
+    images: BTreeMap<(i64, i64), ObjectId>,
+    kids: Vec<Object>,
+}
+
+impl Builder {
+    fn new() -> Builder {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        Builder {
+            doc,
+            pages_id,
+            font_id,
+            images: BTreeMap::new(),
+            kids: Vec::new(),
+        }
+    }
+
+    fn stream(&mut self, content: &str) -> ObjectId {
+        self.doc
+            .add_object(Stream::new(dictionary! {}, content.as_bytes().to_vec()))
+    }
+
+    /// A blank grayscale image, as `(height, width)` like `PageType` reads them.
+    fn image(&mut self, (height, width): (i64, i64)) -> ObjectId {
+        if let Some(id) = self.images.get(&(height, width)) {
+            return *id;
+        }
+
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder
+            .write_all(&vec![255; (height * width) as usize])
+            .expect("writing to memory does not fail");
+        let pixels = encoder.finish().expect("writing to memory does not fail");
+        let dict = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => width,
+            "Height" => height,
+            "ColorSpace" => "DeviceGray",
+            "BitsPerComponent" => 8,
+            "Filter" => "FlateDecode",
+        };
+        let id = self.doc.add_object(Stream::new(dict, pixels));
+        self.images.insert((height, width), id);
+        id
+    }
+
+    /// Adds a page with a link annotation, as every page of the downloads has one.
+    fn page(&mut self, contents: Vec<ObjectId>, xobjects: &[(&str, ObjectId)]) {
+        let (width, height) = PAGE_SIZE;
+        let mut resources = dictionary! { "Font" => dictionary! { "F1" => self.font_id } };
+        if !xobjects.is_empty() {
+            let mut xobject_dict = lopdf::Dictionary::new();
+            for (name, id) in xobjects {
+                xobject_dict.set(*name, *id);
+            }
+            resources.set("XObject", xobject_dict);
+        }
+        let annot = self.doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Link",
+            "Rect" => vec![0.into(), 0.into(), width.into(), 70.into()],
+            "A" => dictionary! {
+                "S" => "URI",
+                "URI" => Object::string_literal("https://example.com/ad"),
+            },
+        });
+        let page = dictionary! {
+            "Type" => "Page",
+            "Parent" => self.pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+            "Resources" => resources,
+            "Contents" => contents.into_iter().map(Object::Reference).collect::<Vec<_>>(),
+            "Annots" => vec![Object::Reference(annot)],
+        };
+        let page_id = self.doc.add_object(page);
+        self.kids.push(page_id.into());
+    }
+
+    /// A page that is a single full-page ad image.
+    fn full_page_ad(&mut self) {
+        let (width, height) = PAGE_SIZE;
+        let ad = self.image(FULL_PAGE_DIMS[0]);
+        let contents = vec![self.stream(&format!("q {} 0 0 {} 0 0 cm /Ad Do Q", width, height))];
+        self.page(contents, &[("Ad", ad)]);
+    }
+
+    fn finish(mut self) -> Document {
+        let count = self.kids.len() as i64;
+        self.doc.objects.insert(
+            self.pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => self.kids,
+                "Count" => count,
+            }),
+        );
+        let catalog = self.doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => self.pages_id,
+        });
+        self.doc.trailer.set("Root", catalog);
+        self.doc
+    }
+}
@@ -0,0 +1,125 @@
+use std::error::Error;
+use std::sync::OnceLock;
+
+use lopdf::{Document, ObjectId};
+
+use crate::models::method::{get_image_xobjects, get_mediabox, get_xobjs, ImageXObject};
+use crate::models::page_type::{PageType, LOGO_DIMS};
+
+#[derive(Clone)]
+/// What detection and cleaning need to know about a page, looked up once.
+///
+/// The images and the MediaBox are resolved the first time they are needed.
+pub struct IndexedPage<'a> {
+    doc: &'a Document,
+    /// The page number (1-based).
+    pub number: u32,
+    pub id: ObjectId,
+    /// The content streams of the page, in drawing order.
+    pub contents: Vec<ObjectId>,
+    /// The image XObjects of the page, or why they couldn't be read.
+    images: OnceLock<Result<Vec<ImageXObject>, String>>,
+    /// The MediaBox of the page, or why it couldn't be read.
+    mediabox: OnceLock<Result<[f32; 4], String>>,
+}
+
+impl<'a> IndexedPage<'a> {
+    fn new(doc: &'a Document, number: u32, id: ObjectId) -> IndexedPage<'a> {
+        IndexedPage {
+            doc,
+            number,
+            id,
+            contents: doc.get_page_contents(id),
+            images: OnceLock::new(),
+            mediabox: OnceLock::new(),
+        }
+    }
+
+    fn images(&self) -> &Result<Vec<ImageXObject>, String> {
+        self.images.get_or_init(|| {
+            let images = match get_xobjs(self.doc, &self.id) {
+                Ok(Some(xobjs)) => get_image_xobjects(self.doc, xobjs),
+                Ok(None) => Ok(Vec::new()),
+                Err(e) => Err(e),
+            };
+            images.map_err(|e| e.to_string())
+        })
+    }
+
+    /// The sizes of the images of the page, as `(height, width)`.
+    pub fn image_sizes(&self) -> Result<Vec<(i64, i64)>, Box<dyn Error>> {
+        let images = self.images().as_ref().map_err(|e| e.as_str())?;
+        Ok(images.iter().map(|x| x.1).collect())
+    }
+
+    /// The type of the page according to the images it contains, as in
+    /// [`PageType::get_page_type`].
+    pub fn page_type(&self) -> Result<PageType, Box<dyn Error>> {
+        Ok(PageType::from_images(&self.image_sizes()?))
+    }
+
+    /// The logo images of the page, if it has any.
+    pub fn logos(&self) -> Vec<ObjectId> {
+        self.images()
+            .iter()
+            .flatten()
+            .filter(|x| LOGO_DIMS.contains(&x.1))
+            .map(|x| x.0)
+            .collect()
+    }
+
+    /// The MediaBox of the page as `[llx, lly, urx, ury]`, as in
+    /// [`get_mediabox`](crate::models::method::get_mediabox).
+    pub fn mediabox(&self) -> Result<[f32; 4], Box<dyn Error>> {
+        let mediabox = self
+            .mediabox
+            .get_or_init(|| get_mediabox(self.doc, self.id).map_err(|e| e.to_string()));
+        Ok(*mediabox.as_ref().map_err(|e| e.as_str())?)
+    }
+}
+
+#[derive(Clone, Default)]
+/// The pages of a document with their contents, images and MediaBox.
+///
+/// The page tree is walked once, when the index is built, and the resources of each page
+/// at most once. The index is shared by the method detection and the planning of every page.
+pub struct PageIndex<'a> {
+    pages: Vec<IndexedPage<'a>>,
+}
+
+impl<'a> PageIndex<'a> {
+    /// Indexes the pages of a document.
+    ///
+    /// # Arguments
+    ///
+    /// * `doc` - The document to index. It is borrowed for as long as the index lives.
+    pub fn new(doc: &'a Document) -> PageIndex<'a> {
+        let pages = doc
+            .get_pages()
+            .into_iter()
+            .map(|(number, id)| IndexedPage::new(doc, number, id))
+            .collect();
+        PageIndex { pages }
+    }
+
+    /// The pages, in order.
+    pub fn pages(&self) -> &[IndexedPage<'a>] {
+        &self.pages
+    }
+
+    /// The page with the given number (1-based).
+    pub fn get(&self, number: u32) -> Option<&IndexedPage<'a>> {
+        number
+            .checked_sub(1)
+            .and_then(|i| self.pages.get(i as usize))
+            .filter(|x| x.number == number)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+}
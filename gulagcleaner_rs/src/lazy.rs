@@ -0,0 +1,1041 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+
+use lopdf::xref::{decode_xref_stream, Xref, XrefEntry, XrefType};
+use lopdf::{Dictionary, Document, Object, ObjectId, ObjectStream, Reader, StringFormat};
+
+use crate::clean::{make_plan, CleanOptions};
+use crate::error::{Error, Result};
+use crate::limits::{check_count, Budget, Limit, Limits};
+use crate::page_tree::PAGE_TREE_DEPTH_LIMIT;
+use crate::progress::Hooks;
+use crate::report::CleanReport;
+use crate::save::set_mod_date;
+use crate::verify::{page_count_issues, Verification};
+
+/// How far from the end of the data `startxref` is searched for.
+const STARTXREF_WINDOW: usize = 1024;
+
+/// Longest trailer dictionary that is parsed.
+const TRAILER_WINDOW: usize = 64 * 1024;
+
+/// The largest object number a document may use, the largest PDF integer. The objects added
+/// by the update are numbered after it, so their numbers can't overflow.
+const MAX_OBJECT_NUMBER: u32 = i32::MAX as u32;
+
+/// How many objects can be in the middle of being read at once. Reading an object reads
+/// its object stream and the `/Length` of a stream, which are never nested deeper.
+const MAX_NESTED_READS: usize = 8;
+
+/// The trailer entries carried over to the trailer of an incremental update.
+const TRAILER_KEYS: [&[u8]; 3] = [b"Root", b"Info", b"ID"];
+
+/// A PDF document whose objects are parsed from its data when they are first read.
+///
+/// Loading reads only the cross-reference sections and the trailer. The data is borrowed,
+/// so it can be a memory-mapped file: only the pages of the file that hold the objects
+/// that are read are ever loaded into memory.
+pub struct LazyDocument<'a> {
+    /// The data, with the cross-reference table of the document. No object is parsed.
+    reader: Reader<'a>,
+    /// Where the last cross-reference section starts.
+    xref_start: usize,
+    /// The kind of the last cross-reference section, used for the incremental update.
+    xref_type: XrefType,
+    /// The objects of the object streams read so far, by object stream number.
+    object_streams: RefCell<HashMap<u32, BTreeMap<ObjectId, Object>>>,
+    /// The objects being read, innermost last, to stop at a reference cycle.
+    reading: RefCell<Vec<ObjectId>>,
+    /// The decompressed bytes left for the streams that are inflated.
+    budget: RefCell<Budget>,
+}
+
+impl<'a> LazyDocument<'a> {
+    /// Reads the cross-reference sections and the trailer of a PDF document, with the
+    /// default `Limits`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The PDF document data.
+    ///
+    /// # Returns
+    ///
+    /// The document, `Error::Parse` if its cross-reference sections can't be read or list
+    /// object numbers above the largest PDF integer, or `Error::Unsupported` if it is
+    /// encrypted.
+    pub fn load(data: &'a [u8]) -> Result<LazyDocument<'a>> {
+        LazyDocument::load_with_limits(data, &Limits::default())
+    }
+
+    /// Reads a PDF document like [`LazyDocument::load`], charging the streams it inflates,
+    /// there and when objects are read, to `limits`.
+    ///
+    /// # Returns
+    ///
+    /// The document, or `Error::LimitExceeded` if a cross-reference stream inflates past
+    /// the limits.
+    pub fn load_with_limits(data: &'a [u8], limits: &Limits) -> Result<LazyDocument<'a>> {
+        let mut budget = Budget::new(limits);
+        let version = header(data).ok_or(Error::Parse(lopdf::Error::Header))?;
+        let (xref_start, xref, xref_type, trailer) = read_xref(data, &mut budget)?;
+        if trailer.has(b"Encrypt") {
+            return Err(Error::Unsupported(
+                "encrypted documents can't be loaded lazily".to_string(),
+            ));
+        }
+
+        if xref.max_id() > MAX_OBJECT_NUMBER {
+            return Err(xref_error(lopdf::XrefError::Parse));
+        }
+        // `/Size` only decides how the new objects are numbered, so an out of range one is
+        // clamped instead of trusted.
+        let size = trailer
+            .get(b"Size")
+            .and_then(Object::as_i64)
+            .unwrap_or_default()
+            .clamp(xref.max_id() as i64 + 1, MAX_OBJECT_NUMBER as i64 + 1);
+        let mut document = Document::with_version(version);
+        document.max_id = (size - 1) as u32;
+        document.trailer = trailer;
+        document.reference_table = xref;
+        Ok(LazyDocument {
+            reader: Reader {
+                buffer: data,
+                document,
+            },
+            xref_start,
+            xref_type,
+            object_streams: RefCell::new(HashMap::new()),
+            reading: RefCell::new(Vec::new()),
+            budget: RefCell::new(budget),
+        })
+    }
+
+    /// The trailer of the document.
+    pub fn trailer(&self) -> &Dictionary {
+        &self.reader.document.trailer
+    }
+
+    /// The number of objects listed in the cross-reference sections.
+    pub fn object_count(&self) -> usize {
+        self.reader.document.reference_table.entries.len()
+    }
+
+    /// Parses an object from the data.
+    ///
+    /// Objects inside an object stream are read from the stream, which is decompressed the
+    /// first time one of its objects is read and kept for the next ones.
+    ///
+    /// # Returns
+    ///
+    /// The object, `Error::Parse` with `lopdf::Error::ReferenceLimit` if reading it needs
+    /// the object itself, as an object stream that contains itself or a stream that is its
+    /// own `/Length` do, or `Error::LimitExceeded` if its object stream inflates past the
+    /// limits.
+    pub fn get_object(&self, id: ObjectId) -> Result<Object> {
+        {
+            let reading = self.reading.borrow();
+            if reading.contains(&id) || reading.len() >= MAX_NESTED_READS {
+                return Err(Error::Parse(lopdf::Error::ReferenceLimit));
+            }
+        }
+        self.reading.borrow_mut().push(id);
+        let object = self.read_object(id);
+        self.reading.borrow_mut().pop();
+        object
+    }
+
+    fn read_object(&self, id: ObjectId) -> Result<Object> {
+        let xref = &self.reader.document.reference_table;
+        match xref.get(id.0) {
+            Some(entry @ XrefEntry::Normal { offset, .. }) => {
+                // lopdf reads an indirect `/Length` while parsing, with no guard against a
+                // cycle, so the object is parsed by a reader that only knows where it is and
+                // `read_stream_content` reads the `/Length` of other objects. lopdf can
+                // still find a stream that is its own `/Length`.
+                if length_reference(self.reader.buffer, *offset as usize) == Some(id) {
+                    return Err(Error::Parse(lopdf::Error::ReferenceLimit));
+                }
+                let mut document = Document::new();
+                document.reference_table.insert(id.0, entry.clone());
+                let reader = Reader {
+                    buffer: self.reader.buffer,
+                    document,
+                };
+                let mut object = reader.get_object(id).map_err(Error::Parse)?;
+                self.read_stream_content(&mut object)?;
+                Ok(object)
+            }
+            Some(XrefEntry::Compressed { container, .. }) if id.1 == 0 => {
+                let container = *container;
+                // Object streams can't be stored in object streams.
+                if !matches!(xref.get(container), Some(XrefEntry::Normal { .. })) {
+                    return Err(Error::Parse(lopdf::Error::ObjectNotFound));
+                }
+                if !self.object_streams.borrow().contains_key(&container) {
+                    let mut stream = match self.get_object((container, 0))? {
+                        Object::Stream(stream) => stream,
+                        _ => return Err(Error::Parse(lopdf::Error::Type)),
+                    };
+                    self.budget.borrow_mut().charge(&stream)?;
+                    let objects = ObjectStream::new(&mut stream)
+                        .map_err(Error::Parse)?
+                        .objects;
+                    self.object_streams.borrow_mut().insert(container, objects);
+                }
+                self.object_streams.borrow()[&container]
+                    .get(&id)
+                    .cloned()
+                    .ok_or(Error::Parse(lopdf::Error::ObjectNotFound))
+            }
+            _ => Err(Error::Parse(lopdf::Error::ObjectNotFound)),
+        }
+    }
+
+    /// Reads an object the cleaner can do without, such as one on a broken page.
+    ///
+    /// # Returns
+    ///
+    /// The object, `None` if it can't be read, or `Error::LimitExceeded` if its object
+    /// stream inflates past the limits.
+    fn get_optional(&self, id: ObjectId) -> Result<Option<Object>> {
+        match self.get_object(id) {
+            Ok(object) => Ok(Some(object)),
+            Err(e @ Error::LimitExceeded { .. }) => Err(e),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Reads the content of a stream whose `/Length` is stored in an object stream, which
+    /// lopdf leaves empty.
+    fn read_stream_content(&self, object: &mut Object) -> Result<()> {
+        let Object::Stream(stream) = object else {
+            return Ok(());
+        };
+        let Some(start) = stream.start_position.filter(|_| stream.content.is_empty()) else {
+            return Ok(());
+        };
+        let length = match stream.dict.get(b"Length").map_err(Error::Parse)? {
+            Object::Reference(id) => self.get_object(*id)?.as_i64(),
+            length => length.as_i64(),
+        };
+        let content = length
+            .ok()
+            .and_then(|length| usize::try_from(length).ok())
+            .and_then(|length| self.reader.buffer.get(start..start.checked_add(length)?))
+            .ok_or(Error::Parse(lopdf::Error::Offset(start)))?;
+        stream.set_content(content.to_vec());
+        Ok(())
+    }
+
+    /// Reads the object `id` into `doc`, if it isn't there already.
+    ///
+    /// # Returns
+    ///
+    /// The object, `None` if it can't be read, or `Error::LimitExceeded` if reading it
+    /// inflates past the limits.
+    fn load_into(&self, doc: &mut Document, id: ObjectId) -> Result<Option<Object>> {
+        if let Some(object) = doc.objects.get(&id) {
+            return Ok(Some(object.clone()));
+        }
+        let Some(object) = self.get_optional(id)? else {
+            return Ok(None);
+        };
+        doc.objects.insert(id, object.clone());
+        Ok(Some(object))
+    }
+
+    /// Reads the value of `key` in `dict` into `doc`, if it is a reference.
+    fn load_entry(
+        &self,
+        doc: &mut Document,
+        dict: &Dictionary,
+        key: &[u8],
+    ) -> Result<Option<Object>> {
+        match dict.get(key) {
+            Ok(Object::Reference(id)) => self.load_into(doc, *id),
+            Ok(value) => Ok(Some(value.clone())),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Reads the parts of the document that cleaning looks at into a sparse `Document`.
+    ///
+    /// That is the catalog, the document information, the page tree, and from each page
+    /// its contents array, its MediaBox and its XObjects. Content streams are not read, and
+    /// XObjects are read without their data.
+    ///
+    /// # Returns
+    ///
+    /// The sparse document, and the XObjects that were read without their data.
+    fn load_structure(&self) -> Result<(Document, HashSet<ObjectId>)> {
+        let trailer = self.trailer();
+        let mut doc = Document::with_version(self.reader.document.version.clone());
+        doc.max_id = self.reader.document.max_id;
+        for key in TRAILER_KEYS {
+            if let Ok(value) = trailer.get(key) {
+                doc.trailer.set(key, value.clone());
+            }
+        }
+        if let Ok(info) = trailer.get(b"Info").and_then(Object::as_reference) {
+            self.load_into(&mut doc, info)?;
+        }
+
+        let root = trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .map_err(Error::Parse)?;
+        let catalog = self
+            .load_into(&mut doc, root)?
+            .ok_or(Error::Parse(lopdf::Error::ObjectNotFound))?;
+        let mut stubs = HashSet::new();
+        if let Ok(pages) = catalog.as_dict().and_then(|x| x.get(b"Pages")) {
+            if let Ok(pages) = pages.as_reference() {
+                self.load_node(&mut doc, pages, &mut stubs, &mut HashSet::new(), 0)?;
+            }
+        }
+        Ok((doc, stubs))
+    }
+
+    /// Reads a page tree node and the nodes and pages below it.
+    fn load_node(
+        &self,
+        doc: &mut Document,
+        id: ObjectId,
+        stubs: &mut HashSet<ObjectId>,
+        visited: &mut HashSet<ObjectId>,
+        depth: usize,
+    ) -> Result<()> {
+        if depth > PAGE_TREE_DEPTH_LIMIT || !visited.insert(id) {
+            return Ok(());
+        }
+        let Some(Object::Dictionary(node)) = self.load_into(doc, id)? else {
+            return Ok(());
+        };
+
+        // Pages inherit their resources and MediaBox from the nodes above them.
+        if let Some(Object::Dictionary(resources)) = self.load_entry(doc, &node, b"Resources")? {
+            if let Some(Object::Dictionary(xobjects)) =
+                self.load_entry(doc, &resources, b"XObject")?
+            {
+                for (_, xobject) in xobjects.iter() {
+                    if let Ok(xobject) = xobject.as_reference() {
+                        self.load_stub(doc, xobject, stubs)?;
+                    }
+                }
+            }
+        }
+        if let Some(Object::Array(mediabox)) = self.load_entry(doc, &node, b"MediaBox")? {
+            for value in mediabox {
+                if let Ok(value) = value.as_reference() {
+                    self.load_into(doc, value)?;
+                }
+            }
+        }
+
+        match node.type_name() {
+            Ok("Pages") => {
+                let kids = node.get(b"Kids").and_then(Object::as_array);
+                for kid in kids.into_iter().flatten() {
+                    if let Ok(kid) = kid.as_reference() {
+                        self.load_node(doc, kid, stubs, visited, depth + 1)?;
+                    }
+                }
+            }
+            Ok("Page") => {
+                // An indirect contents array is needed to list the content streams, but
+                // the streams themselves are not.
+                if let Ok(contents) = node.get(b"Contents").and_then(Object::as_reference) {
+                    if let Some(array @ Object::Array(_)) = self.get_optional(contents)? {
+                        doc.objects.insert(contents, array);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Reads an XObject without its data.
+    fn load_stub(
+        &self,
+        doc: &mut Document,
+        id: ObjectId,
+        stubs: &mut HashSet<ObjectId>,
+    ) -> Result<()> {
+        if doc.objects.contains_key(&id) {
+            return Ok(());
+        }
+        if let Some(mut object) = self.get_optional(id)? {
+            if let Ok(stream) = object.as_stream_mut() {
+                stream.content = Vec::new();
+                stubs.insert(id);
+            }
+            doc.objects.insert(id, object);
+        }
+        Ok(())
+    }
+
+    /// Appends an incremental update with the given objects to the document data.
+    ///
+    /// # Arguments
+    ///
+    /// * `doc` - The sparse document the changed objects are taken from.
+    /// * `changed` - The objects to write.
+    /// * `freed` - The objects to mark as deleted.
+    /// * `target` - Where to write the document data and the update.
+    fn write_update<W: Write>(
+        &self,
+        doc: &Document,
+        changed: &[ObjectId],
+        freed: &[ObjectId],
+        target: &mut W,
+    ) -> std::io::Result<()> {
+        let data = self.reader.buffer;
+        target.write_all(data)?;
+        let mut position = data.len();
+        if !data.ends_with(b"\n") {
+            target.write_all(b"\n")?;
+            position += 1;
+        }
+
+        // Offsets of the written objects, or `None` for the freed ones.
+        let mut entries: BTreeMap<u32, (Option<usize>, u16)> = BTreeMap::new();
+        for id in changed {
+            let Some(object) = doc.objects.get(id) else {
+                continue;
+            };
+            let mut bytes = format!("{} {} obj\n", id.0, id.1).into_bytes();
+            write_object(&mut bytes, object);
+            bytes.extend_from_slice(b"\nendobj\n");
+            target.write_all(&bytes)?;
+            entries.insert(id.0, (Some(position), id.1));
+            position += bytes.len();
+        }
+        for id in freed {
+            entries.insert(id.0, (None, id.1.saturating_add(1)));
+        }
+
+        let mut trailer = Dictionary::new();
+        for key in TRAILER_KEYS {
+            if let Ok(value) = doc.trailer.get(key) {
+                trailer.set(key, value.clone());
+            }
+        }
+        trailer.set("Prev", self.xref_start as i64);
+
+        let xref_start = position;
+        match self.xref_type {
+            XrefType::CrossReferenceTable => {
+                let size = entries.keys().last().map_or(0, |x| *x as i64 + 1);
+                trailer.set("Size", (self.reader.document.max_id as i64 + 1).max(size));
+                let mut bytes = b"xref\n".to_vec();
+                for (start, run) in runs(&entries) {
+                    bytes.extend(format!("{} {}\n", start, run.len()).bytes());
+                    for (offset, generation) in run {
+                        let line = match offset {
+                            Some(offset) => format!("{:010} {:05} n\r\n", offset, generation),
+                            None => format!("{:010} {:05} f\r\n", 0, generation),
+                        };
+                        bytes.extend(line.bytes());
+                    }
+                }
+                bytes.extend_from_slice(b"trailer\n");
+                write_object(&mut bytes, &Object::Dictionary(trailer));
+                target.write_all(&bytes)?;
+            }
+            XrefType::CrossReferenceStream => {
+                // The cross-reference stream lists itself.
+                let id = doc
+                    .max_id
+                    .max(self.reader.document.max_id)
+                    .checked_add(1)
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            "no object number is left for the cross-reference stream",
+                        )
+                    })?;
+                entries.insert(id, (Some(xref_start), 0));
+                let width = (usize::BITS - xref_start.leading_zeros())
+                    .div_ceil(8)
+                    .max(1);
+                let mut content = Vec::new();
+                for (offset, generation) in entries.values() {
+                    content.push(offset.is_some() as u8);
+                    let offset = offset.unwrap_or_default().to_be_bytes();
+                    content.extend_from_slice(&offset[offset.len() - width as usize..]);
+                    content.extend_from_slice(&generation.to_be_bytes());
+                }
+                let index: Vec<Object> = runs(&entries)
+                    .flat_map(|(start, run)| [start as i64, run.len() as i64])
+                    .map(Object::Integer)
+                    .collect();
+                trailer.set("Type", "XRef");
+                trailer.set("Size", id as i64 + 1);
+                trailer.set("Index", index);
+                trailer.set("W", vec![1.into(), (width as i64).into(), 2.into()]);
+                trailer.set("Length", content.len() as i64);
+
+                let mut bytes = format!("{} 0 obj\n", id).into_bytes();
+                write_object(&mut bytes, &Object::Dictionary(trailer));
+                bytes.extend_from_slice(b"stream\n");
+                bytes.extend_from_slice(&content);
+                bytes.extend_from_slice(b"\nendstream\nendobj");
+                target.write_all(&bytes)?;
+            }
+        }
+        write!(target, "\nstartxref\n{}\n%%EOF\n", xref_start)
+    }
+}
+
+/// Cleans a PDF document without loading all of it, and writes the result to `target`.
+///
+/// Only the page tree, the contents arrays, MediaBoxes and XObject dictionaries of the pages
+/// are parsed, plus the content streams of the pages whose contents are rewritten. The
+/// output is the original data followed by an incremental update with the changed objects,
+/// and it is written as it is produced.
+///
+/// Since the text and the content streams of the kept pages are not read, the text of the
+/// document is not compared and the verification only checks the number of pages. The
+/// cross-reference and object streams, and the content streams of the pages that are
+/// changed, are charged to `options.limits` before they are inflated.
+///
+/// # Arguments
+///
+/// * `data` - The PDF document data, which can be a memory-mapped file.
+/// * `target` - Where to write the cleaned document.
+/// * `options` - The options that control the cleaning.
+///
+/// # Returns
+///
+/// A `CleanReport` describing the run.
+pub fn clean_lazy<W: Write>(
+    data: &[u8],
+    target: &mut W,
+    options: &CleanOptions,
+) -> Result<CleanReport> {
+    clean_lazy_with_hooks(data, target, options, &Hooks::default())
+}
+
+/// Cleans a PDF document like [`clean_lazy`], reporting progress and checking for
+/// cancellation between pages.
+///
+/// Nothing is written to `target` if the run is cancelled.
+pub fn clean_lazy_with_hooks<W: Write>(
+    data: &[u8],
+    target: &mut W,
+    options: &CleanOptions,
+    hooks: &Hooks,
+) -> Result<CleanReport> {
+    options.validate()?;
+    let lazy = LazyDocument::load_with_limits(data, &options.limits)?;
+    check_count(
+        Limit::Objects,
+        lazy.object_count(),
+        options.limits.max_objects,
+    )?;
+    let (mut doc, mut stubs) = lazy.load_structure()?;
+    let pages_before = doc.get_pages().len() as u32;
+    if pages_before == 0 {
+        return Err(Error::Unsupported("the document has no pages".to_string()));
+    }
+    check_count(
+        Limit::Pages,
+        pages_before as usize,
+        options.limits.max_pages,
+    )?;
+
+    let plan = make_plan(&doc, options, hooks)?;
+
+    // Read what the plan changes beyond the structure: the content streams it wraps and
+    // the images it hides.
+    for page_plan in plan.pages.iter().filter(|x| x.content_transform.is_some()) {
+        for id in doc.get_page_contents(page_plan.id) {
+            if let Some(Object::Stream(stream)) = lazy.load_into(&mut doc, id)? {
+                lazy.budget.borrow_mut().charge(&stream)?;
+            }
+        }
+    }
+    for id in plan.pages.iter().flat_map(|x| &x.drop_xobjects) {
+        if stubs.remove(id) {
+            if let Some(object) = lazy.get_optional(*id)? {
+                doc.objects.insert(*id, object);
+            }
+        }
+    }
+
+    let before = doc.objects.clone();
+    let mut warnings = plan.warnings.clone();
+    warnings.extend(plan.apply_with_hooks(&mut doc, hooks)?);
+    if let Some(timestamp) = options.mod_date {
+        set_mod_date(&mut doc, timestamp)?;
+    }
+
+    // XObjects read without their data are never written back.
+    let changed: Vec<ObjectId> = doc
+        .objects
+        .iter()
+        .filter(|(id, object)| !stubs.contains(id) && before.get(id) != Some(object))
+        .map(|(id, _)| *id)
+        .collect();
+    let freed: Vec<ObjectId> = before
+        .keys()
+        .filter(|id| !doc.objects.contains_key(id))
+        .copied()
+        .collect();
+    lazy.write_update(&doc, &changed, &freed, target)
+        .map_err(Error::Io)?;
+
+    let deleted_pages = plan.deleted_pages(pages_before);
+    let pages_after = pages_before - deleted_pages.len() as u32;
+    let issues = page_count_issues(pages_after, doc.get_pages().len() as u32);
+
+    Ok(CleanReport {
+        method_code: plan.method_code,
+        pages_before,
+        pages_after,
+        deleted_pages,
+        verification: Verification::from_issues(issues),
+        warnings,
+        ..Default::default()
+    })
+}
+
+fn xref_error(e: lopdf::XrefError) -> Error {
+    Error::Parse(lopdf::Error::Xref(e))
+}
+
+/// The version in the `%PDF-` header of the data.
+fn header(data: &[u8]) -> Option<String> {
+    let line = data.strip_prefix(b"%PDF-")?;
+    let end = line.iter().position(|x| *x == b'\r' || *x == b'\n')?;
+    String::from_utf8(line[..end].trim_ascii().to_vec()).ok()
+}
+
+/// The offset in the last `startxref` of the data.
+fn startxref(data: &[u8]) -> Option<usize> {
+    let from = data.len().saturating_sub(STARTXREF_WINDOW);
+    let at = data[from..]
+        .windows(b"startxref".len())
+        .rposition(|x| x == b"startxref")?;
+    let mut position = from + at + b"startxref".len();
+    read_integer(data, &mut position).and_then(|x| usize::try_from(x).ok())
+}
+
+/// The value of an offset entry of a trailer, such as `/Prev`.
+fn offset_entry(trailer: &Dictionary, key: &[u8]) -> Option<usize> {
+    let offset = trailer.get(key).and_then(Object::as_i64).ok()?;
+    usize::try_from(offset).ok()
+}
+
+/// Skips the whitespace at `position`.
+fn skip_whitespace(data: &[u8], position: &mut usize) {
+    while data
+        .get(*position)
+        .is_some_and(|x| b" \t\r\n\x0C\0".contains(x))
+    {
+        *position += 1;
+    }
+}
+
+/// Reads the unsigned integer at `position`, after any whitespace.
+fn read_integer(data: &[u8], position: &mut usize) -> Option<u64> {
+    skip_whitespace(data, position);
+    let start = *position;
+    while data.get(*position).is_some_and(u8::is_ascii_digit) {
+        *position += 1;
+    }
+    std::str::from_utf8(&data[start..*position])
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Reads the cross-reference sections of a PDF document, from its last `startxref` along the
+/// `/Prev` and `/XRefStm` entries of their trailers.
+///
+/// Cross-reference streams are charged to `budget` before they are inflated.
+///
+/// # Returns
+///
+/// The offset of the last section, the merged sections, the type of the last one, and the
+/// last trailer.
+pub(crate) fn read_xref(
+    data: &[u8],
+    budget: &mut Budget,
+) -> Result<(usize, Xref, XrefType, Dictionary)> {
+    let xref_start = startxref(data).ok_or(xref_error(lopdf::XrefError::Start))?;
+    let mut xref = Xref::new(0, XrefType::CrossReferenceTable);
+    let mut trailer = None;
+    let mut xref_type = None;
+    let mut next = Some(xref_start);
+    let mut seen = HashSet::new();
+    while let Some(offset) = next {
+        if !seen.insert(offset) {
+            return Err(xref_error(lopdf::XrefError::PrevStart));
+        }
+        let (section, section_trailer) = read_section(data, offset, budget)?;
+        xref_type.get_or_insert(section.cross_reference_type);
+        xref.merge(section);
+        // Hybrid files list the objects in object streams in a separate section.
+        if let Some(offset) = offset_entry(&section_trailer, b"XRefStm") {
+            xref.merge(read_section(data, offset, budget)?.0);
+        }
+        next = offset_entry(&section_trailer, b"Prev");
+        trailer.get_or_insert(section_trailer);
+    }
+    Ok((
+        xref_start,
+        xref,
+        xref_type.unwrap_or(XrefType::CrossReferenceTable),
+        trailer.unwrap_or_default(),
+    ))
+}
+
+/// Reads the cross-reference section at `offset`, a table or a stream, and its trailer.
+///
+/// A cross-reference stream is charged to `budget` before it is inflated.
+fn read_section(data: &[u8], offset: usize, budget: &mut Budget) -> Result<(Xref, Dictionary)> {
+    let mut position = offset;
+    skip_whitespace(data, &mut position);
+    let rest = data
+        .get(position..)
+        .ok_or(xref_error(lopdf::XrefError::Start))?;
+    if rest.starts_with(b"xref") {
+        position += b"xref".len();
+        let xref = read_table(data, &mut position).ok_or(xref_error(lopdf::XrefError::Parse))?;
+        let trailer = read_trailer(data, position).ok_or(xref_error(lopdf::XrefError::Parse))?;
+        return Ok((xref, trailer));
+    }
+
+    // A cross-reference stream, `N G obj << ... >> stream ... endstream`.
+    let id = read_integer(data, &mut position).and_then(|x| u32::try_from(x).ok());
+    let generation = read_integer(data, &mut position).and_then(|x| u16::try_from(x).ok());
+    let (Some(id), Some(generation)) = (id, generation) else {
+        return Err(xref_error(lopdf::XrefError::Parse));
+    };
+    if length_reference(data, offset) == Some((id, generation)) {
+        return Err(Error::Parse(lopdf::Error::ReferenceLimit));
+    }
+    let mut document = Document::new();
+    document.reference_table.insert(
+        id,
+        XrefEntry::Normal {
+            offset: u32::try_from(offset).map_err(|_| xref_error(lopdf::XrefError::Start))?,
+            generation,
+        },
+    );
+    let reader = Reader {
+        buffer: data,
+        document,
+    };
+    match reader.get_object((id, generation)).map_err(Error::Parse)? {
+        Object::Stream(stream) => {
+            budget.charge(&stream)?;
+            decode_xref_stream(stream).map_err(Error::Parse)
+        }
+        _ => Err(xref_error(lopdf::XrefError::Parse)),
+    }
+}
+
+/// The object that the `/Length` of the stream at `offset` refers to, if the object there
+/// is a stream whose length is an indirect reference.
+///
+/// lopdf reads that object while it parses the stream, so a stream whose length leads back
+/// to itself is read forever. The tokens of the object are read up to its `stream` or
+/// `endobj` keyword, skipping strings and comments.
+fn length_reference(data: &[u8], offset: usize) -> Option<ObjectId> {
+    let is_whitespace = |x: u8| b" \t\r\n\x0C\0".contains(&x);
+    let is_delimiter = |x: u8| b"()<>[]{}/%".contains(&x);
+
+    let mut length = None;
+    // Whether the last key of the stream dictionary is `/Length`.
+    let mut is_length = false;
+    let (mut dictionaries, mut arrays) = (0, 0);
+    // The last regular tokens, to match `N G R`.
+    let mut last: Vec<&[u8]> = Vec::new();
+    let mut position = offset;
+    while let Some(&x) = data.get(position) {
+        let start = position;
+        position += 1;
+        match x {
+            _ if is_whitespace(x) => continue,
+            // lopdf allows comments inside a reference.
+            b'%' => {
+                while data.get(position).is_some_and(|x| !b"\r\n".contains(x)) {
+                    position += 1;
+                }
+                continue;
+            }
+            b'(' => {
+                let mut depth = 1;
+                while depth > 0 {
+                    match data.get(position) {
+                        Some(b'\\') => position += 1,
+                        Some(b'(') => depth += 1,
+                        Some(b')') => depth -= 1,
+                        Some(_) => {}
+                        None => return None,
+                    }
+                    position += 1;
+                }
+            }
+            b'<' if data.get(position) != Some(&b'<') => {
+                while data.get(position).is_some_and(|x| *x != b'>') {
+                    position += 1;
+                }
+                position += 1;
+            }
+            b'<' => {
+                position += 1;
+                dictionaries += 1;
+            }
+            b'>' if data.get(position) == Some(&b'>') => {
+                position += 1;
+                dictionaries -= 1;
+            }
+            b'[' => arrays += 1,
+            b']' => arrays -= 1,
+            b'/' => {
+                while data
+                    .get(position)
+                    .is_some_and(|x| !is_whitespace(*x) && !is_delimiter(*x))
+                {
+                    position += 1;
+                }
+                if dictionaries == 1 && arrays == 0 {
+                    is_length = &data[start..position] == b"/Length";
+                }
+            }
+            _ if is_delimiter(x) => {}
+            _ => {
+                while data
+                    .get(position)
+                    .is_some_and(|x| !is_whitespace(*x) && !is_delimiter(*x))
+                {
+                    position += 1;
+                }
+                let token = &data[start..position];
+                match token {
+                    b"stream" => return length,
+                    b"endobj" => return None,
+                    _ => {}
+                }
+                // lopdf reads `N G R` without a space before the `R` too.
+                let (digits, is_reference) = match token.strip_suffix(b"R") {
+                    Some(digits) if digits.iter().all(u8::is_ascii_digit) => (digits, true),
+                    _ => (token, false),
+                };
+                if !digits.is_empty() {
+                    last.push(digits);
+                    if last.len() > 2 {
+                        last.remove(0);
+                    }
+                }
+                if is_reference && is_length && dictionaries == 1 && arrays == 0 {
+                    length = match last[..] {
+                        [id, generation] => parse_number::<u32>(id).zip(parse_number(generation)),
+                        _ => None,
+                    };
+                }
+                if is_reference {
+                    last.clear();
+                }
+                continue;
+            }
+        }
+        last.clear();
+    }
+    None
+}
+
+/// The unsigned integer in `digits`, as lopdf reads it.
+fn parse_number<T: std::str::FromStr>(digits: &[u8]) -> Option<T> {
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+/// Checks that lopdf can read every object of `xref` without following the `/Length` of
+/// its streams in a cycle, or deeper than `MAX_NESTED_READS` objects.
+///
+/// # Returns
+///
+/// `Error::Parse` with `lopdf::Error::ReferenceLimit` if a stream would be read forever, or
+/// deep enough to overflow the stack.
+pub(crate) fn check_length_references(data: &[u8], xref: &Xref) -> Result<()> {
+    let next = |id: ObjectId| match xref.get(id.0) {
+        Some(XrefEntry::Normal { offset, generation }) if *generation == id.1 => {
+            length_reference(data, *offset as usize)
+        }
+        _ => None,
+    };
+    let mut checked = HashSet::new();
+    for (number, entry) in &xref.entries {
+        let XrefEntry::Normal { generation, .. } = entry else {
+            continue;
+        };
+        let mut chain = vec![(*number, *generation)];
+        while let Some(id) = next(chain[chain.len() - 1]) {
+            if checked.contains(&id) {
+                break;
+            }
+            if chain.contains(&id) || chain.len() >= MAX_NESTED_READS {
+                return Err(Error::Parse(lopdf::Error::ReferenceLimit));
+            }
+            chain.push(id);
+        }
+        checked.extend(chain);
+    }
+    Ok(())
+}
+
+/// Reads the subsections of a cross-reference table, up to its `trailer` keyword.
+fn read_table(data: &[u8], position: &mut usize) -> Option<Xref> {
+    let mut xref = Xref::new(0, XrefType::CrossReferenceTable);
+    loop {
+        skip_whitespace(data, position);
+        if data.get(*position..)?.starts_with(b"trailer") {
+            *position += b"trailer".len();
+            return Some(xref);
+        }
+        let start = u32::try_from(read_integer(data, position)?).ok()?;
+        let count = u32::try_from(read_integer(data, position)?).ok()?;
+        for id in start..start.checked_add(count)? {
+            let offset = u32::try_from(read_integer(data, position)?).ok()?;
+            let generation = u16::try_from(read_integer(data, position)?).ok()?;
+            skip_whitespace(data, position);
+            let entry = match data.get(*position)? {
+                b'n' => XrefEntry::Normal { offset, generation },
+                b'f' => XrefEntry::Free,
+                _ => return None,
+            };
+            *position += 1;
+            // Free entries are kept so they hide the object in older sections.
+            if id != 0 {
+                xref.insert(id, entry);
+            }
+        }
+    }
+}
+
+/// Parses the trailer dictionary at `position`.
+fn read_trailer(data: &[u8], position: usize) -> Option<Dictionary> {
+    // lopdf only parses whole indirect objects, so the dictionary is wrapped in one.
+    let end = data.len().min(position.saturating_add(TRAILER_WINDOW));
+    let mut buffer = b"1 0 obj\n".to_vec();
+    buffer.extend_from_slice(data.get(position..end)?);
+    let mut document = Document::new();
+    document.reference_table.insert(
+        1,
+        XrefEntry::Normal {
+            offset: 0,
+            generation: 0,
+        },
+    );
+    let reader = Reader {
+        buffer: &buffer,
+        document,
+    };
+    match reader.get_object((1, 0)).ok()? {
+        Object::Dictionary(dict) => Some(dict),
+        _ => None,
+    }
+}
+
+/// Splits cross-reference entries into runs of consecutive object numbers.
+fn runs<T>(entries: &BTreeMap<u32, T>) -> impl Iterator<Item = (u32, Vec<&T>)> {
+    let mut runs: Vec<(u32, Vec<&T>)> = Vec::new();
+    for (id, entry) in entries {
+        match runs.last_mut() {
+            Some((start, run)) if *start + run.len() as u32 == *id => run.push(entry),
+            _ => runs.push((*id, vec![entry])),
+        }
+    }
+    runs.into_iter()
+}
+
+/// Serialises an object as lopdf does, with a stream's `/Length` set to its data.
+pub(crate) fn write_object(out: &mut Vec<u8>, object: &Object) {
+    match object {
+        Object::Null => out.extend_from_slice(b"null"),
+        Object::Boolean(value) => out.extend_from_slice(if *value { b"true" } else { b"false" }),
+        Object::Integer(value) => out.extend(value.to_string().bytes()),
+        Object::Real(value) => out.extend(value.to_string().bytes()),
+        Object::Name(name) => write_name(out, name),
+        Object::String(text, StringFormat::Literal) => {
+            out.push(b'(');
+            for &byte in text {
+                match byte {
+                    b'(' | b')' | b'\\' => out.extend_from_slice(&[b'\\', byte]),
+                    b'\r' => out.extend_from_slice(b"\\r"),
+                    _ => out.push(byte),
+                }
+            }
+            out.push(b')');
+        }
+        Object::String(text, StringFormat::Hexadecimal) => {
+            out.push(b'<');
+            for byte in text {
+                out.extend(format!("{:02X}", byte).bytes());
+            }
+            out.push(b'>');
+        }
+        Object::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 && needs_separator(item) {
+                    out.push(b' ');
+                }
+                write_object(out, item);
+            }
+            out.push(b']');
+        }
+        Object::Dictionary(dict) => write_dictionary(out, dict),
+        Object::Stream(stream) => {
+            let mut dict = stream.dict.clone();
+            dict.set("Length", stream.content.len() as i64);
+            write_dictionary(out, &dict);
+            out.extend_from_slice(b"stream\n");
+            out.extend_from_slice(&stream.content);
+            out.extend_from_slice(b"\nendstream");
+        }
+        Object::Reference(id) => out.extend(format!("{} {} R", id.0, id.1).bytes()),
+    }
+}
+
+/// Whether a space must be written before an object that follows another token.
+fn needs_separator(object: &Object) -> bool {
+    matches!(
+        object,
+        Object::Null
+            | Object::Boolean(_)
+            | Object::Integer(_)
+            | Object::Real(_)
+            | Object::Reference(_)
+    )
+}
+
+fn write_dictionary(out: &mut Vec<u8>, dict: &Dictionary) {
+    out.extend_from_slice(b"<<");
+    for (key, value) in dict {
+        write_name(out, key);
+        if needs_separator(value) {
+            out.push(b' ');
+        }
+        write_object(out, value);
+    }
+    out.extend_from_slice(b">>");
+}
+
+fn write_name(out: &mut Vec<u8>, name: &[u8]) {
+    out.push(b'/');
+    for &byte in name {
+        // Whitespace, delimiters and bytes outside of `!` to `~` are written as # sequences.
+        if b" \t\n\r\x0C()<>[]{}/%#".contains(&byte) || !(33..=126).contains(&byte) {
+            out.extend(format!("#{:02X}", byte).bytes());
+        } else {
+            out.push(byte);
+        }
+    }
+}
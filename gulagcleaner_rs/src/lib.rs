@@ -9,22 +9,106 @@
 
  # Examples
 
-    ```rust
-    use gulagcleaner_rs::clean::clean_pdf;
-
-    fn main(){
-        let data = std::fs::read("example_docs/wuolah-free-example.pdf").unwrap();
-        let (clean_pdf, _) = clean_pdf(data, false);
-        //Stores the clean pdf in the out directory
-        std::fs::write("example_docs/out/wuolah_clean.pdf", clean_pdf).unwrap();
-    }
+    ```rust,no_run
+    use gulagcleaner_rs::{clean_pdf_with_report, CleanOptions};
+
+    let data = std::fs::read("example_docs/wuolah-free-example.pdf").unwrap();
+    let (clean_pdf, _) = clean_pdf_with_report(&data, &CleanOptions::default()).unwrap();
+    //Stores the clean pdf in the out directory
+    std::fs::write("example_docs/out/wuolah_clean.pdf", clean_pdf).unwrap();
+    ```
+
+ If you already hold a `lopdf::Document`, it can be cleaned in place:
+
+    ```rust,no_run
+    use gulagcleaner_rs::{clean_document, CleanOptions};
+    use lopdf::Document;
+
+    let mut doc = Document::load("example_docs/wuolah-free-example.pdf").unwrap();
+    let report = clean_document(&mut doc, &CleanOptions::default()).unwrap();
+    println!("Removed {} pages", report.deleted_pages.len());
+    doc.save("example_docs/out/wuolah_clean.pdf").unwrap();
     ```
 */
 /// Main method execution
 pub mod clean;
 
 /// Main method rexport
+#[allow(deprecated)]
 pub use clean::clean_pdf;
+pub use clean::{
+    apply_plan, apply_plan_with_hooks, clean_document, clean_document_with_hooks,
+    clean_pdf_with_hooks, clean_pdf_with_report, clean_reader, clean_to_writer, load_document,
+    load_document_with_limits, plan_document, plan_document_with_hooks, CleanOptions,
+};
+
+/// Deterministic serialisation of cleaned documents
+pub mod save;
+
+pub use save::save_document;
+
+/// Serializable cleaning plans, decided before any change is made
+pub mod plan;
+
+pub use plan::{CleanPlan, PagePlan};
+
+/// Per-page data looked up once and shared by detection and planning
+pub mod index;
+
+pub use index::PageIndex;
+
+/// Per-page work spread over a thread pool when the `parallel` feature is enabled
+mod parallel;
+
+/// Batch deletion of pages from the page tree
+pub mod page_tree;
+
+/// Read-only detection of the method and page types
+pub mod analyze;
+
+pub use analyze::{analyze_document, analyze_document_with_hooks};
+
+/// Cover-page metadata of downloaded documents
+pub mod metadata;
+
+pub use metadata::{extract_metadata, DocumentMetadata};
+
+/// Progress reporting and cancellation of a cleaning run
+pub mod progress;
+
+pub use progress::{CancellationToken, Hooks, Progress, Stage};
+
+/// Bounds on the resources a document may use
+pub mod limits;
+
+pub use limits::{Limit, Limits};
+
+/// Errors returned by the cleaner
+pub mod error;
+
+pub use error::{Error, Result};
+
+/// The `lopdf` version used by the document-level API
+pub use lopdf;
+
+/// Summary of a cleaning run
+pub mod report;
+
+/// Text extraction and comparison between the original and cleaned documents
+pub mod text;
+
+/// Post-clean verification of the output document
+pub mod verify;
+
+/// Anonymized structural skeleton of a document, for bug reports
+pub mod skeleton;
+
+pub use skeleton::{export_skeleton, export_skeleton_pdf};
+
+/// Cleaning of large documents without loading them whole, written as an incremental update
+pub mod lazy;
+
+pub use lazy::{clean_lazy, clean_lazy_with_hooks, LazyDocument};
 
 /// Modeling the different pdf sources and types
 pub mod models {
@@ -35,5 +119,17 @@ pub mod models {
     pub mod page_type;
 }
 
+/// Synthetic documents shaped like each platform's downloads, for tests
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
+
 #[cfg(test)]
 pub mod tests;
+
+/// Structural snapshots of the cleaned documents
+#[cfg(test)]
+mod snapshots;
+
+/// Property tests that malformed documents never make the cleaner panic
+#[cfg(test)]
+mod robustness;
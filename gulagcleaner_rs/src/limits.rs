@@ -0,0 +1,320 @@
+use crate::error::{Error, Result};
+use crate::lazy::{check_length_references, read_xref};
+
+use lopdf::{Document, Object, ObjectId, ObjectStream, Reader, Stream};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// Name given to object streams while they are measured, so lopdf doesn't inflate them
+/// while parsing.
+const PENDING_OBJECT_STREAM: &str = "GulagPendingObjStm";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+/// Bounds on the resources a document may use, to protect against huge inputs and
+/// decompression bombs.
+///
+/// The limits are checked when the document is loaded, before any stream is inflated, and
+/// again before the cleaned document is saved.
+pub struct Limits {
+    /// Maximum number of pages.
+    pub max_pages: usize,
+    /// Maximum number of objects, including those stored in object streams.
+    pub max_objects: usize,
+    /// Maximum number of bytes inflated from all the streams the cleaner reads.
+    pub max_decompressed_bytes: usize,
+    /// Maximum number of bytes inflated from a single stream.
+    pub max_stream_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_pages: 5_000,
+            max_objects: 1_000_000,
+            max_decompressed_bytes: 512 * 1024 * 1024,
+            max_stream_bytes: 128 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// The limit that a document exceeded.
+pub enum Limit {
+    Pages,
+    Objects,
+    DecompressedBytes,
+    StreamBytes,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Pages => write!(f, "pages"),
+            Limit::Objects => write!(f, "objects"),
+            Limit::DecompressedBytes => write!(f, "decompressed bytes"),
+            Limit::StreamBytes => write!(f, "decompressed bytes in a single stream"),
+        }
+    }
+}
+
+impl Limits {
+    /// Limits that are never exceeded.
+    pub fn unlimited() -> Limits {
+        Limits {
+            max_pages: usize::MAX,
+            max_objects: usize::MAX,
+            max_decompressed_bytes: usize::MAX,
+            max_stream_bytes: usize::MAX,
+        }
+    }
+
+    /// Checks an already loaded document against the limits.
+    ///
+    /// The page contents and `/ToUnicode` maps, which are the streams the cleaner inflates,
+    /// are measured without keeping their decompressed data.
+    ///
+    /// # Returns
+    ///
+    /// `Error::LimitExceeded` with the first limit the document exceeds.
+    pub fn check(&self, doc: &Document) -> Result<()> {
+        self.check_streams(doc, &mut Budget::new(self))
+    }
+
+    /// Checks the page and object counts of `doc`, which don't need any stream to be inflated.
+    pub(crate) fn check_counts(&self, doc: &Document) -> Result<()> {
+        check_count(Limit::Objects, doc.objects.len(), self.max_objects)?;
+        check_count(Limit::Pages, doc.get_pages().len(), self.max_pages)
+    }
+
+    fn check_streams(&self, doc: &Document, budget: &mut Budget) -> Result<()> {
+        self.check_counts(doc)?;
+
+        let mut measured = HashSet::new();
+        for page in doc.page_iter() {
+            let fonts = doc.get_page_fonts(page);
+            let cmaps = fonts
+                .values()
+                .filter_map(|font| match font.get(b"ToUnicode") {
+                    Ok(Object::Reference(id)) => Some(*id),
+                    _ => None,
+                });
+            for id in doc.get_page_contents(page).into_iter().chain(cmaps) {
+                if !measured.insert(id) {
+                    continue;
+                }
+                if let Ok(stream) = doc.get_object(id).and_then(Object::as_stream) {
+                    budget.charge(stream)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses `data` into a document, measuring the cross-reference and object streams before
+    /// lopdf inflates them.
+    ///
+    /// The document is otherwise identical to the one returned by `Document::load_mem`. The
+    /// streams read by the cleaner are only measured by [`Limits::check_loaded`], once the
+    /// document has been decrypted.
+    pub(crate) fn load(&self, data: &[u8]) -> Result<(Document, Budget)> {
+        let mut budget = Budget::new(self);
+        // lopdf inflates the cross-reference streams as it reads them, so they are measured
+        // on a first pass, which also finds the streams lopdf would read forever. Sections
+        // that pass can't read are left for lopdf to report.
+        match read_xref(data, &mut budget) {
+            Ok((_, xref, _, _)) => check_length_references(data, &xref)?,
+            Err(e @ Error::LimitExceeded { .. }) => return Err(e),
+            Err(_) => {}
+        }
+        let reader = Reader {
+            buffer: data,
+            document: Document::new(),
+        };
+        let mut doc = reader
+            .read(Some(hide_object_stream))
+            .map_err(Error::Parse)?;
+        check_count(Limit::Objects, doc.objects.len(), self.max_objects)?;
+
+        let pending: Vec<ObjectId> = doc
+            .objects
+            .iter()
+            .filter(|(_, x)| {
+                x.as_stream()
+                    .is_ok_and(|x| x.dict.type_name().ok() == Some(PENDING_OBJECT_STREAM))
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for id in pending {
+            let Ok(stream) = doc.get_object_mut(id).and_then(Object::as_stream_mut) else {
+                continue;
+            };
+            budget.charge(stream)?;
+            stream.dict.set("Type", "ObjStm");
+
+            // Same as lopdf: broken object streams are dropped, and their objects never
+            // replace objects stored outside of them.
+            match ObjectStream::new(stream) {
+                Ok(objects) => {
+                    for (id, object) in objects.objects {
+                        doc.objects.entry(id).or_insert(object);
+                    }
+                }
+                Err(_) => {
+                    doc.objects.remove(&id);
+                }
+            }
+            check_count(Limit::Objects, doc.objects.len(), self.max_objects)?;
+        }
+        Ok((doc, budget))
+    }
+
+    /// Checks a document returned by [`Limits::load`], sharing the budget of its object
+    /// streams.
+    pub(crate) fn check_loaded(&self, doc: &Document, mut budget: Budget) -> Result<()> {
+        self.check_streams(doc, &mut budget)
+    }
+}
+
+pub(crate) fn check_count(limit: Limit, count: usize, max: usize) -> Result<()> {
+    if count > max {
+        return Err(Error::LimitExceeded { limit, max });
+    }
+    Ok(())
+}
+
+/// Renames object streams so that the reader keeps them compressed.
+fn hide_object_stream(id: ObjectId, object: &mut Object) -> Option<(ObjectId, Object)> {
+    if let Ok(stream) = object.as_stream_mut() {
+        if stream.dict.type_is(b"ObjStm") {
+            stream.dict.set("Type", PENDING_OBJECT_STREAM);
+        }
+    }
+    // The reader keeps `object` and only checks that the result is `Some`.
+    Some((id, Object::Null))
+}
+
+/// The decompressed bytes left to a document.
+pub(crate) struct Budget {
+    max_stream_bytes: usize,
+    max_decompressed_bytes: usize,
+    remaining: usize,
+}
+
+impl Budget {
+    pub(crate) fn new(limits: &Limits) -> Budget {
+        Budget {
+            max_stream_bytes: limits.max_stream_bytes,
+            max_decompressed_bytes: limits.max_decompressed_bytes,
+            remaining: limits.max_decompressed_bytes,
+        }
+    }
+
+    /// Measures the decompressed size of `stream` and takes it from the budget.
+    pub(crate) fn charge(&mut self, stream: &Stream) -> Result<()> {
+        let bound = self.max_stream_bytes.min(self.remaining);
+        match decoded_len(stream, bound) {
+            Some(len) => {
+                self.remaining -= len;
+                Ok(())
+            }
+            None if bound == self.max_stream_bytes => Err(Error::LimitExceeded {
+                limit: Limit::StreamBytes,
+                max: self.max_stream_bytes,
+            }),
+            None => Err(Error::LimitExceeded {
+                limit: Limit::DecompressedBytes,
+                max: self.max_decompressed_bytes,
+            }),
+        }
+    }
+}
+
+/// The size of the data lopdf would inflate from `stream`, or `None` if it is larger than
+/// `max`.
+///
+/// Streams that lopdf doesn't inflate (images, and filters other than Flate and LZW) count
+/// with their stored size.
+fn decoded_len(stream: &Stream, max: usize) -> Option<usize> {
+    let filters = stream.filters().unwrap_or_default();
+    let is_image = stream
+        .dict
+        .get(b"Subtype")
+        .and_then(Object::as_name_str)
+        .ok()
+        == Some("Image");
+    let decodable = filters
+        .iter()
+        .all(|x| matches!(x.as_str(), "FlateDecode" | "LZWDecode"));
+    if is_image || filters.is_empty() || !decodable {
+        return (stream.content.len() <= max).then_some(stream.content.len());
+    }
+
+    let early_change = stream
+        .dict
+        .get(b"DecodeParms")
+        .and_then(Object::as_dict)
+        .and_then(|x| x.get(b"EarlyChange"))
+        .and_then(Object::as_i64)
+        .map_or(true, |x| x != 0);
+
+    let mut data = Cow::Borrowed(stream.content.as_slice());
+    for filter in filters {
+        let mut output = BoundedWriter::new(max);
+        if filter == "FlateDecode" {
+            if !data.is_empty() {
+                let limit = max.saturating_add(1) as u64;
+                let mut decoder = flate2::read::ZlibDecoder::new(data.as_ref()).take(limit);
+                // Like lopdf, keep whatever was inflated before a corrupt block.
+                let _ = io::copy(&mut decoder, &mut output);
+            }
+        } else {
+            let mut decoder = if early_change {
+                weezl::decode::Decoder::with_tiff_size_switch(weezl::BitOrder::Msb, 8)
+            } else {
+                weezl::decode::Decoder::new(weezl::BitOrder::Msb, 8)
+            };
+            let _ = decoder.into_stream(&mut output).decode_all(data.as_ref());
+        }
+        if output.exceeded {
+            return None;
+        }
+        data = Cow::Owned(output.data);
+    }
+    Some(data.len())
+}
+
+/// Collects at most `max` bytes, failing the write that would go over.
+struct BoundedWriter {
+    data: Vec<u8>,
+    max: usize,
+    exceeded: bool,
+}
+
+impl BoundedWriter {
+    fn new(max: usize) -> BoundedWriter {
+        BoundedWriter {
+            data: Vec::new(),
+            max,
+            exceeded: false,
+        }
+    }
+}
+
+impl Write for BoundedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.data.len() + buf.len() > self.max {
+            self.exceeded = true;
+            return Err(io::Error::other("limit exceeded"));
+        }
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
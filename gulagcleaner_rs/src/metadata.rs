@@ -0,0 +1,59 @@
+use lopdf::Document;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::text::extract_page_text;
+
+/// Number of cover lines that hold the metadata.
+const METADATA_LINES: usize = 6;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+/// The details printed on the cover page of a downloaded document.
+pub struct DocumentMetadata {
+    /// The name of the file, as uploaded.
+    pub file_name: String,
+    pub author: String,
+    pub subject: String,
+    /// The course and degree.
+    pub course: String,
+    pub faculty: String,
+    pub university: String,
+}
+
+/// Extracts the metadata from the cover page of a document, without modifying it.
+///
+/// The first six non-empty lines of text on the first page are read, in the order the
+/// platforms print them.
+///
+/// # Arguments
+///
+/// * `doc` - A reference to the `Document` to read.
+///
+/// # Returns
+///
+/// The `DocumentMetadata`, or `Error::MissingMetadata` if the first page doesn't have enough text.
+pub fn extract_metadata(doc: &Document) -> Result<DocumentMetadata> {
+    let lines: Vec<String> = doc
+        .get_pages()
+        .get(&1)
+        .map(|id| extract_page_text(doc, *id))
+        .unwrap_or_default()
+        .iter()
+        .flat_map(|x| x.lines())
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .take(METADATA_LINES)
+        .collect();
+
+    match <[String; METADATA_LINES]>::try_from(lines) {
+        Ok([file_name, author, subject, course, faculty, university]) => Ok(DocumentMetadata {
+            file_name,
+            author,
+            subject,
+            course,
+            faculty,
+            university,
+        }),
+        Err(lines) => Err(Error::MissingMetadata { found: lines.len() }),
+    }
+}
@@ -2,7 +2,18 @@ use std::{collections::HashSet, error::Error};
 
 use lopdf::{Dictionary, Document, Object, ObjectId};
 
-use crate::{clean::Cleaner, models::page_type};
+use crate::{
+    clean::Cleaner,
+    index::{IndexedPage, PageIndex},
+    models::page_type,
+    parallel,
+    plan::{CleanPlan, PagePlan},
+    progress::{Hooks, Stage},
+    report::Warning,
+};
+
+/// The matrix that scales a Naive banner page so its notes fill the cropped page.
+const BANNER_TRANSFORM: [f32; 6] = [1.124, 0.0, 0.0, 1.124, 0.0, 0.0];
 
 pub enum Method {
     /// The Wuolah method, which takes a vector of vectors of tuples containing unsigned integers and unsigned shorts,
@@ -15,204 +26,230 @@ pub enum Method {
     Naive,
 }
 
+impl Method {
+    /// The code of the method: 0 for "Wuolah", 1 for "StuDocu", and 2 for "Naive".
+    pub fn code(&self) -> u8 {
+        match self {
+            Method::Wuolah(..) => 0,
+            Method::StuDocu(_) => 1,
+            Method::Naive => 2,
+        }
+    }
+}
+
+/// The name of the method with the given code: "Wuolah", "StuDocu", "Naive" or "Unknown".
+pub fn method_name(method_code: u8) -> &'static str {
+    match method_code {
+        0 => "Wuolah",
+        1 => "StuDocu",
+        2 => "Naive",
+        _ => "Unknown",
+    }
+}
+
 /// Implements the `Cleaner` trait for the `Method` enum.
-/// This method cleans the document based on the selected method.
-/// It modifies the contents and properties of the document's pages.
-/// Returns a tuple containing the pages to delete and a status code.
+/// This method decides how to clean the document based on the selected method.
+/// It plans the changes to the contents and properties of the document's pages, and the pages to delete.
+///
+/// A page that can't be processed is left out of the plan and reported as a `Warning`, so a single
+/// malformed page does not abort the whole document.
 impl Cleaner for Method {
-    fn clean(&mut self, doc: &mut Document) -> (Vec<u32>, u8) {
+    fn plan(&self, index: &PageIndex, hooks: &Hooks) -> crate::Result<CleanPlan> {
+        let mut plan = CleanPlan {
+            method_code: self.code(),
+            ..Default::default()
+        };
+        let total = index.len() as u32;
+
         match self {
             Method::Wuolah(content_list, to_delete) => {
-                let new_contents: Vec<Vec<(u32, u16)>> = content_list
-                    .iter()
-                    .enumerate()
-                    .map(|(i, x)| {
-                        let pares = if x == content_list.last().unwrap() {
-                            find_iobj_pairs(x, &content_list[i - 1])
-                        } else {
-                            let check_if_00 = find_iobj_pairs(x, &content_list[i + 1]);
-                            if check_if_00 != (0, 0) {
-                                check_if_00
-                            } else {
-                                find_iobj_pairs(x, &content_list[i - 1])
-                            }
-                        };
-
-                        x[(pares.0) - 2..=(pares.1) + 3].to_vec()
-                    })
-                    .collect();
-
-                let pages = doc.get_pages();
-
-                let vector: Vec<(&u32, &(u32, u16))> = pages
+                let vector: Vec<&IndexedPage> = index
+                    .pages()
                     .iter()
-                    .filter(|x| doc.get_page_contents(*x.1).len() > 3)
+                    .filter(|x| x.contents.len() > 3)
                     .collect();
                 for (i, page) in vector.iter().enumerate() {
-                    let mutable_page = doc.get_object_mut(*page.1).unwrap().as_dict_mut().unwrap();
-                    let contents_objects: Vec<Object> = new_contents[i]
-                        .iter()
-                        .map(|x| Object::Reference(*x))
-                        .collect();
-
-                    mutable_page.set(*b"Contents", lopdf::Object::Array(contents_objects));
-
-                    mutable_page.set("Annots", Object::Array(vec![]));
-                    let mediabox = mutable_page.get(b"MediaBox").unwrap().as_array().unwrap();
-
-                    let height_offset = match mediabox[1].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[1].as_i64().unwrap() as f32,
-                    };
-                    let width_offset = match mediabox[0].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[0].as_i64().unwrap() as f32,
-                    };
-
-                    let height = match mediabox[3].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[3].as_i64().unwrap() as f32,
-                    };
-                    let width = match mediabox[2].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[2].as_i64().unwrap() as f32,
-                    };
-
-                    for _box in ["MediaBox", "ArtBox", "TrimBox", "CropBox", "BleedBox"] {
-                        mutable_page.set(
-                            _box,
-                            Object::Array(vec![
-                                Object::Real(0.0),
-                                Object::Real(0.0),
-                                Object::Real(width - width_offset),
-                                Object::Real(height - height_offset),
-                            ]),
-                        );
+                    hooks.page(Stage::Classifying, page.number, total)?;
+                    match plan_wuolah_page(page, content_list, i) {
+                        Ok(page_plan) => plan.pages.push(page_plan),
+                        Err(e) => plan.skip_page(page.number, e),
                     }
                 }
 
-                (to_delete.to_vec(), 0)
+                plan.delete = to_delete.to_vec();
             }
             Method::StuDocu(content_list) => {
                 let new_contents: Vec<Vec<(u32, u16)>> =
                     content_list.iter().skip(1).map(|x| vec![x[1]]).collect();
-                let pages = doc.get_pages();
-                let vector: Vec<(&u32, &(u32, u16))> = pages.iter().filter(|x| *x.0 != 1).collect();
+                let vector: Vec<&IndexedPage> =
+                    index.pages().iter().filter(|x| x.number != 1).collect();
                 for (i, page) in vector.iter().enumerate() {
-                    let mutable_page = doc.get_object_mut(*page.1).unwrap().as_dict_mut().unwrap();
-                    let contents_objects: Vec<Object> = new_contents[i]
-                        .iter()
-                        .map(|x| Object::Reference(*x))
-                        .collect();
-
-                    mutable_page.set(*b"Contents", lopdf::Object::Array(contents_objects));
-
-                    mutable_page.set("Annots", Object::Array(vec![]));
+                    hooks.page(Stage::Classifying, page.number, total)?;
+                    plan.pages.push(PagePlan {
+                        contents: Some(new_contents[i].clone()),
+                        clear_annotations: true,
+                        ..PagePlan::new(page.number, page.id)
+                    });
                 }
-                (vec![1], 1)
+                plan.delete = vec![1];
             }
 
             Method::Naive => {
-                println!("Using naive method");
-                let mut to_delete = Vec::new();
-                let pages = doc.get_pages();
-                for page in &pages {
-                    let page_type =
-                        page_type::PageType::get_page_type(doc, page.1).unwrap_or_default();
-                    let mutable_page = doc.get_object_mut(*page.1).unwrap().as_dict_mut().unwrap();
-
-                    let mediabox = mutable_page.get(b"MediaBox").unwrap().as_array().unwrap();
-                    let height_offset = match mediabox[1].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[1].as_i64().unwrap() as f32,
-                    };
-                    let width_offset = match mediabox[0].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[0].as_i64().unwrap() as f32,
-                    };
-
-                    let height = match mediabox[3].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[3].as_i64().unwrap() as f32,
-                    };
-                    let width = match mediabox[2].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[2].as_i64().unwrap() as f32,
-                    };
-
-                    match page_type {
-                        page_type::PageType::FullPageAds => to_delete.push(*page.0),
-                        page_type::PageType::Idk => to_delete.push(*page.0),
-                        page_type::PageType::BannerAds => {
-                            //1.141
-                            let scale = 1.124;
-                            for _box in ["MediaBox", "ArtBox", "TrimBox", "CropBox", "BleedBox"] {
-                                mutable_page.set(
-                                    _box,
-                                    Object::Array(vec![
-                                        Object::Real(
-                                            0.164 * (width - width_offset) + width_offset * scale,
-                                        ),
-                                        Object::Real(
-                                            0.031 * (height - height_offset)
-                                                + height_offset * scale,
-                                        ),
-                                        Object::Real(
-                                            0.978 * (width - width_offset) * scale
-                                                + width_offset * scale,
-                                        ),
-                                        Object::Real(
-                                            0.865 * (height - height_offset) * scale
-                                                + height_offset * scale,
-                                        ),
-                                    ]),
-                                );
-                            }
-
-                            let mut contents = doc.get_page_content(*page.1).unwrap();
-                            let mut new_contents = Vec::new();
-                            let c_prepend = "q\n1.124 0 0 1.124 0 0 cm\n".as_bytes();
-                            let c_append = "Q".as_bytes();
-
-                            new_contents.extend_from_slice(c_prepend);
-                            new_contents.append(&mut contents);
-                            new_contents.extend_from_slice(c_append);
-
-                            doc.change_page_content(*page.1, new_contents).unwrap()
-                        }
-                        page_type::PageType::Watermark => {
-                            for _box in ["MediaBox", "ArtBox", "TrimBox", "CropBox", "BleedBox"] {
-                                mutable_page.set(
-                                    _box,
-                                    Object::Array(vec![
-                                        Object::Real(0.015 * (width - width_offset) + width_offset),
-                                        Object::Real(
-                                            0.05 * (height - height_offset) + height_offset,
-                                        ),
-                                        Object::Real(0.95 * (width - width_offset) + width_offset),
-                                        Object::Real(
-                                            0.98 * (height - height_offset) + height_offset,
-                                        ),
-                                    ]),
-                                );
-                            }
+                // The pages are classified independently, then added to the plan in order.
+                parallel::for_each_chunked(
+                    index.pages(),
+                    &mut plan,
+                    hooks.cancel,
+                    |_, page| plan_naive_page(page).map_err(|e| e.to_string()),
+                    |plan, page, planned| {
+                        hooks.page(Stage::Classifying, page.number, total)?;
+                        match planned {
+                            Ok(NaivePage::Plan(page_plan)) => plan.pages.push(page_plan),
+                            Ok(NaivePage::Delete) => plan.delete.push(page.number),
+                            Ok(NaivePage::NoImages) => plan
+                                .warnings
+                                .push(Warning::PageWithoutImages { page: page.number }),
+                            Err(e) => plan.skip_page(page.number, e),
                         }
-                    }
-                }
+                        Ok(())
+                    },
+                )?;
+            }
+        }
+        Ok(plan)
+    }
+}
 
-                for page in &pages {
-                    // remove the logo
-                    let _ = remove_logo(doc, page.1);
+/// Plans a Wuolah page: keep only the notes' content streams and reset its boxes to the origin.
+///
+/// # Arguments
+///
+/// * `page` - The page.
+/// * `content_list` - The content streams of every page with ads, in page order.
+/// * `i` - The position of the page in `content_list`.
+fn plan_wuolah_page(
+    page: &IndexedPage,
+    content_list: &[Vec<(u32, u16)>],
+    i: usize,
+) -> Result<PagePlan, Box<dyn Error>> {
+    let x = content_list.get(i).ok_or("page has no content list")?;
+    let previous = i.checked_sub(1).map(|i| &content_list[i]);
+    let next = content_list.get(i + 1);
+
+    let pares = match (next, previous) {
+        (Some(next), previous) => {
+            let check_if_00 = find_iobj_pairs(x, next);
+            match previous {
+                Some(previous) if check_if_00 == (0, 0) => find_iobj_pairs(x, previous),
+                _ => check_if_00,
+            }
+        }
+        (None, Some(previous)) => find_iobj_pairs(x, previous),
+        (None, None) => return Err("no neighbouring page to compare contents with".into()),
+    };
+    let new_contents = pares
+        .0
+        .checked_sub(2)
+        .and_then(|start| x.get(start..=pares.1 + 3))
+        .ok_or("could not locate the shared ad content streams")?
+        .to_vec();
+
+    let [width_offset, height_offset, width, height] = page.mediabox()?;
+
+    Ok(PagePlan {
+        contents: Some(new_contents),
+        boxes: Some([0.0, 0.0, width - width_offset, height - height_offset]),
+        clear_annotations: true,
+        ..PagePlan::new(page.number, page.id)
+    })
+}
 
-                    // remove the annotations
-                    let mutable_page = doc.get_object_mut(*page.1).unwrap().as_dict_mut().unwrap();
-                    mutable_page.set("Annots", Object::Array(vec![]));
-                }
+/// What the Naive method does with a page.
+enum NaivePage {
+    Plan(PagePlan),
+    /// The page is an ad (or has only unknown images) and is deleted.
+    Delete,
+    /// The page has no images, so it is text the Naive method can't judge, and is kept.
+    NoImages,
+}
 
-                (to_delete, 2)
-            }
+/// Plans a Naive page from the images it contains.
+fn plan_naive_page(page: &IndexedPage) -> Result<NaivePage, Box<dyn Error>> {
+    let mut page_plan = PagePlan::new(page.number, page.id);
+    if page.image_sizes()?.is_empty() {
+        return Ok(NaivePage::NoImages);
+    }
+    match page.page_type()? {
+        page_type::PageType::FullPageAds | page_type::PageType::Idk => {
+            return Ok(NaivePage::Delete)
+        }
+        page_type::PageType::BannerAds => {
+            page_plan.boxes = Some(banner_boxes(page)?);
+            page_plan.content_transform = Some(BANNER_TRANSFORM);
         }
+        page_type::PageType::Watermark => page_plan.boxes = Some(watermark_boxes(page)?),
     }
+
+    // remove the logo
+    page_plan.drop_xobjects = page.logos();
+
+    // remove the annotations
+    page_plan.clear_annotations = true;
+    Ok(NaivePage::Plan(page_plan))
+}
+
+/// The boxes that crop the banners out of a Naive page once its contents are scaled.
+fn banner_boxes(page: &IndexedPage) -> Result<[f32; 4], Box<dyn Error>> {
+    let [width_offset, height_offset, width, height] = page.mediabox()?;
+
+    //1.141
+    let scale = BANNER_TRANSFORM[0];
+    Ok([
+        0.164 * (width - width_offset) + width_offset * scale,
+        0.031 * (height - height_offset) + height_offset * scale,
+        0.978 * (width - width_offset) * scale + width_offset * scale,
+        0.865 * (height - height_offset) * scale + height_offset * scale,
+    ])
+}
+
+/// The boxes that crop the watermark margins out of a Naive page.
+fn watermark_boxes(page: &IndexedPage) -> Result<[f32; 4], Box<dyn Error>> {
+    let [width_offset, height_offset, width, height] = page.mediabox()?;
+    Ok([
+        0.015 * (width - width_offset) + width_offset,
+        0.05 * (height - height_offset) + height_offset,
+        0.95 * (width - width_offset) + width_offset,
+        0.98 * (height - height_offset) + height_offset,
+    ])
+}
+
+/// Gets the MediaBox of a page as `[llx, lly, urx, ury]`, following references and
+/// inheriting it from the page tree if the page does not define its own.
+pub fn get_mediabox(doc: &Document, page: ObjectId) -> Result<[f32; 4], Box<dyn Error>> {
+    let mut node = doc.get_dictionary(page)?;
+    let mediabox = loop {
+        if let Ok(mediabox) = node.get(b"MediaBox") {
+            break doc.dereference(mediabox)?.1.as_array()?;
+        }
+        let parent = node
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .map_err(|_| "page has no MediaBox")?;
+        node = doc.get_dictionary(parent)?;
+    };
+
+    if mediabox.len() != 4 {
+        return Err(format!("MediaBox has {} entries instead of 4", mediabox.len()).into());
+    }
+    let mut rect = [0.0; 4];
+    for (value, object) in rect.iter_mut().zip(mediabox) {
+        *value = match doc.dereference(object)?.1 {
+            Object::Integer(x) => *x as f32,
+            Object::Real(x) => *x,
+            _ => return Err("MediaBox has a non-numeric entry".into()),
+        };
+    }
+    Ok(rect)
 }
 
 pub fn find_iobj_pairs(first_page: &[(u32, u16)], second_page: &[(u32, u16)]) -> (usize, usize) {
@@ -244,86 +281,78 @@ pub fn find_iobj_pairs(first_page: &[(u32, u16)], second_page: &[(u32, u16)]) ->
     }
 }
 
-pub fn remove_logo(doc: &mut Document, page: &ObjectId) -> Result<(), Box<dyn Error>> {
-    let xobjs = get_xobjs(doc, page)?.clone();
-    let images = get_images(doc, &xobjs)?;
-
-    // let has_logo = !page_type::LOGO_DIMS
-    //     .iter()
-    //     .collect::<HashSet<_>>()
-    //     .intersection(&images.iter().collect::<HashSet<_>>())
-    //     .collect::<Vec<_>>()
-    //     .is_empty();
-
-    let has_logo = images
-        .iter()
-        .any(|image| page_type::LOGO_DIMS.contains(image));
-
-    if !has_logo {
-        return Ok(());
-    }
-    for obj in &xobjs {
-        let objectdict = get_objdict(doc, obj)?;
-
-        let subtype = objectdict.get(b"Subtype")?.as_name()?;
+/// Gets the logo images of a page, if it has any.
+pub fn get_logos(doc: &Document, page: &ObjectId) -> Result<Vec<ObjectId>, Box<dyn Error>> {
+    let Some(xobjs) = get_xobjs(doc, page)? else {
+        return Ok(Vec::new());
+    };
 
-        let sub_s = String::from_utf8_lossy(subtype);
-        if sub_s.starts_with("Image")
-            && page_type::LOGO_DIMS.contains(&(
-                objectdict.get(b"Height")?.as_i64()?,
-                objectdict.get(b"Width")?.as_i64()?,
-            ))
-        {
-            let mutable_page = &mut doc
-                .get_object_mut(obj.1.as_reference()?)?
-                .as_stream_mut()?
-                .dict;
-            mutable_page.set(*b"Height", 0);
-        }
-    }
-    Ok(())
+    Ok(get_image_xobjects(doc, xobjs)?
+        .into_iter()
+        .filter(|x| page_type::LOGO_DIMS.contains(&x.1))
+        .map(|x| x.0)
+        .collect())
 }
 
 fn get_objdict<'a>(
     doc: &'a Document,
     obj: (&Vec<u8>, &Object),
 ) -> Result<&'a Dictionary, Box<dyn Error>> {
-    let objdict = &doc
-        .get_object(obj.1.as_reference().unwrap())?
-        .as_stream()?
-        .dict;
+    let objdict = &doc.get_object(obj.1.as_reference()?)?.as_stream()?.dict;
 
     Ok(objdict)
 }
 
-pub fn get_xobjs<'a>(doc: &'a Document, page: &ObjectId) -> Result<&'a Dictionary, Box<dyn Error>> {
+/// Gets the XObject dictionary of a page, or `None` if its resources don't define one.
+pub fn get_xobjs<'a>(
+    doc: &'a Document,
+    page: &ObjectId,
+) -> Result<Option<&'a Dictionary>, Box<dyn Error>> {
     let resource = doc.get_page_resources(*page);
-    let resource_dict: &Dictionary = if resource.1.is_empty() {
-        resource.0.unwrap()
-    } else {
-        doc.get_object(resource.1[0])?.as_dict()?
+    let resource_dict: &Dictionary = match (resource.0, resource.1.first()) {
+        (Some(x), _) => x,
+        (None, Some(x)) => doc.get_object(*x)?.as_dict()?,
+        (None, None) => return Err("page has no resources".into()),
     };
-    let xobjs = match resource_dict.get(b"XObject")? {
-        Object::Dictionary(x) => x,
-        Object::Reference(x) => doc.get_object(*x)?.as_dict()?,
-        _ => return Err("Error".into()),
+    let xobjs = match resource_dict.get(b"XObject") {
+        Ok(Object::Dictionary(x)) => x,
+        Ok(Object::Reference(x)) => doc.get_object(*x)?.as_dict()?,
+        Ok(_) => return Err("XObject resource is not a dictionary".into()),
+        Err(_) => return Ok(None),
     };
-    Ok(xobjs)
+    Ok(Some(xobjs))
 }
 
 pub fn get_images(doc: &Document, xobjs: &Dictionary) -> Result<Vec<(i64, i64)>, Box<dyn Error>> {
+    Ok(get_image_xobjects(doc, xobjs)?
+        .into_iter()
+        .map(|x| x.1)
+        .collect())
+}
+
+/// An image XObject as `(id, (height, width))`.
+pub type ImageXObject = (ObjectId, (i64, i64));
+
+/// Gets the images of an XObject dictionary.
+pub fn get_image_xobjects(
+    doc: &Document,
+    xobjs: &Dictionary,
+) -> Result<Vec<ImageXObject>, Box<dyn Error>> {
     let mut images = Vec::new();
 
     for obj in xobjs {
         let objectdict = get_objdict(doc, obj)?;
 
-        let subtype = objectdict.get(b"Subtype").unwrap().as_name().unwrap();
+        let subtype = objectdict.get(b"Subtype")?.as_name()?;
         let sub_s = String::from_utf8_lossy(subtype);
 
         if sub_s.starts_with("Image") {
             images.push((
-                objectdict.get(b"Height").unwrap().as_i64().unwrap(),
-                objectdict.get(b"Width").unwrap().as_i64().unwrap(),
+                obj.1.as_reference()?,
+                (
+                    objectdict.get(b"Height")?.as_i64()?,
+                    objectdict.get(b"Width")?.as_i64()?,
+                ),
             ));
         }
     }
@@ -1,10 +1,11 @@
 use std::{collections::HashSet, error::Error};
 
 use lopdf::{Document, ObjectId};
+use serde::{Deserialize, Serialize};
 
 use super::method::{get_images, get_xobjs};
 
-#[derive(Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 /// Represents the different methods used in the Gulag Cleaner application.
 pub enum PageType {
     BannerAds,
@@ -16,7 +17,7 @@ pub enum PageType {
 
 pub const LOGO_DIMS: [(i64, i64); 3] = [(71, 390), (37, 203), (73, 390)];
 
-const HORIZONTAL_BANNER_DIMS: [(i64, i64); 7] = [
+pub const HORIZONTAL_BANNER_DIMS: [(i64, i64); 7] = [
     (247, 1414),
     (213, 1219),
     (215, 1219),
@@ -25,7 +26,7 @@ const HORIZONTAL_BANNER_DIMS: [(i64, i64); 7] = [
     (147, 1757),
     (221, 1240),
 ];
-const VERTICAL_BANNER_DIMS: [(i64, i64); 8] = [
+pub const VERTICAL_BANNER_DIMS: [(i64, i64); 8] = [
     (1753, 170),
     (1518, 248),
     (1520, 147),
@@ -35,7 +36,7 @@ const VERTICAL_BANNER_DIMS: [(i64, i64); 8] = [
     (1093, 217),
     (1534, 150),
 ];
-const FULL_PAGE_DIMS: [(i64, i64); 7] = [
+pub const FULL_PAGE_DIMS: [(i64, i64); 7] = [
     (842, 595),
     (1754, 1240),
     (2526, 1785),
@@ -62,8 +63,15 @@ impl PageType {
     ///
     /// A `Result` containing the `PageType` of the page if successful, or a `Box<dyn Error>` if an error occurs.
     pub fn get_page_type(doc: &Document, page: &ObjectId) -> Result<PageType, Box<dyn Error>> {
-        let xobjs = get_xobjs(doc, page)?;
-        let images = get_images(doc, xobjs)?;
+        let images = match get_xobjs(doc, page)? {
+            Some(xobjs) => get_images(doc, xobjs)?,
+            None => Vec::new(),
+        };
+        Ok(PageType::from_images(&images))
+    }
+
+    /// Get the type of a page from the sizes of its images, as `(height, width)`.
+    pub fn from_images(images: &[(i64, i64)]) -> PageType {
         let has_logo = !LOGO_DIMS
             .iter()
             .collect::<HashSet<_>>()
@@ -93,13 +101,13 @@ impl PageType {
             .is_empty();
 
         if has_horizontal_banner && has_vertical_banner {
-            Ok(PageType::BannerAds)
+            PageType::BannerAds
         } else if has_full_page {
-            Ok(PageType::FullPageAds)
+            PageType::FullPageAds
         } else if has_logo {
-            Ok(PageType::Watermark)
+            PageType::Watermark
         } else {
-            Ok(PageType::Idk)
+            PageType::Idk
         }
     }
 }
@@ -0,0 +1,139 @@
+use std::collections::{BTreeSet, HashSet};
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+/// Deepest page tree node that is rebuilt, as deep as lopdf walks the tree.
+pub(crate) const PAGE_TREE_DEPTH_LIMIT: usize = 256;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// The outcome of deleting a set of pages.
+pub struct PageDeletion {
+    /// The page numbers (1-based) that were deleted, sorted and without duplicates.
+    pub deleted: Vec<u32>,
+    /// The page numbers that the document doesn't have, sorted and without duplicates.
+    pub out_of_range: Vec<u32>,
+}
+
+/// Deletes a set of pages from a document in a single pass.
+///
+/// The page numbers refer to the document before any page is deleted, and may be given in
+/// any order and more than once. The page objects and every reference to them are removed,
+/// then the page tree is rebuilt once: the `/Kids` of each node keep only the pages that
+/// are left, `/Count` is recomputed, and the nodes left without pages are removed.
+///
+/// # Arguments
+///
+/// * `doc` - The document to delete the pages from.
+/// * `pages` - The page numbers (1-based) to delete.
+///
+/// # Returns
+///
+/// The pages that were deleted and the ones that were out of range.
+pub fn delete_pages(doc: &mut Document, pages: &[u32]) -> PageDeletion {
+    let page_ids = doc.get_pages();
+    let page_count = page_ids.len() as u32;
+    let (deleted, out_of_range): (BTreeSet<u32>, BTreeSet<u32>) = pages
+        .iter()
+        .partition(|page| (1..=page_count).contains(*page));
+
+    let ids: HashSet<ObjectId> = deleted.iter().map(|page| page_ids[page]).collect();
+    if !ids.is_empty() {
+        for id in &ids {
+            doc.objects.remove(id);
+        }
+        for object in doc.objects.values_mut() {
+            remove_references(object, &ids);
+        }
+        let root = doc
+            .catalog()
+            .and_then(|x| x.get(b"Pages"))
+            .and_then(Object::as_reference);
+        if let Ok(root) = root {
+            rebuild_node(doc, root, &mut HashSet::new(), 0);
+        }
+    }
+
+    PageDeletion {
+        deleted: deleted.into_iter().collect(),
+        out_of_range: out_of_range.into_iter().collect(),
+    }
+}
+
+/// Removes the array items and dictionary entries inside `object` that refer to `ids`.
+fn remove_references(object: &mut Object, ids: &HashSet<ObjectId>) {
+    let is_removed = |x: &Object| x.as_reference().is_ok_and(|x| ids.contains(&x));
+    let remove_from_dict = |dict: &mut Dictionary| {
+        let keys: Vec<Vec<u8>> = dict
+            .iter()
+            .filter(|(_, x)| is_removed(x))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            dict.remove(&key);
+        }
+        dict.iter_mut().for_each(|(_, x)| remove_references(x, ids));
+    };
+    match object {
+        Object::Array(items) => {
+            items.retain(|x| !is_removed(x));
+            items.iter_mut().for_each(|x| remove_references(x, ids));
+        }
+        Object::Dictionary(dict) => remove_from_dict(dict),
+        Object::Stream(stream) => remove_from_dict(&mut stream.dict),
+        _ => {}
+    }
+}
+
+/// Rebuilds the `/Kids` and `/Count` of a page tree node and of the nodes below it,
+/// removing the nodes that are left without pages.
+///
+/// # Returns
+///
+/// The number of pages below the node, or `None` if the node was already visited or is
+/// too deep, in which case it is left as it is.
+fn rebuild_node(
+    doc: &mut Document,
+    id: ObjectId,
+    visited: &mut HashSet<ObjectId>,
+    depth: usize,
+) -> Option<i64> {
+    if depth > PAGE_TREE_DEPTH_LIMIT || !visited.insert(id) {
+        return None;
+    }
+    let kids = doc
+        .get_dictionary(id)
+        .and_then(|x| x.get(b"Kids"))
+        .and_then(Object::as_array)
+        .ok()?
+        .clone();
+
+    let mut count = 0;
+    let mut new_kids = Vec::with_capacity(kids.len());
+    for kid in kids {
+        let node = kid.as_reference().ok().and_then(|kid_id| {
+            let type_name = doc.get_dictionary(kid_id).and_then(Dictionary::type_name);
+            Some((kid_id, type_name.ok()?.to_string()))
+        });
+        match node {
+            Some((_, type_name)) if type_name == "Page" => count += 1,
+            Some((kid_id, type_name)) if type_name == "Pages" => {
+                match rebuild_node(doc, kid_id, visited, depth + 1) {
+                    Some(0) => {
+                        doc.objects.remove(&kid_id);
+                        continue;
+                    }
+                    Some(kid_count) => count += kid_count,
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+        new_kids.push(kid);
+    }
+
+    if let Ok(node) = doc.get_dictionary_mut(id) {
+        node.set("Kids", new_kids);
+        node.set("Count", count);
+    }
+    Some(count)
+}
@@ -0,0 +1,74 @@
+use crate::error::{Error, Result};
+use crate::progress::CancellationToken;
+
+/// Pages given to each thread of the pool in a chunk.
+#[cfg(feature = "parallel")]
+const PAGES_PER_THREAD: usize = 4;
+
+/// The number of items mapped at a time by [`for_each_chunked`].
+pub(crate) fn chunk_size() -> usize {
+    #[cfg(feature = "parallel")]
+    {
+        rayon::current_num_threads() * PAGES_PER_THREAD
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        1
+    }
+}
+
+/// Maps `f` over `items`, on the rayon thread pool when the `parallel` feature is enabled.
+///
+/// The results are in the order of `items` either way.
+fn map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        items.par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        items.iter().map(f).collect()
+    }
+}
+
+/// Maps `f` over `items` a chunk at a time, and hands each result to `done` in the order of
+/// `items` as soon as its chunk is finished.
+///
+/// `f` reads `state` while a chunk is mapped, and `done` can then change it. `f` is not
+/// called once `cancel` is set, and an error returned by `done` stops the run before the
+/// next chunk, so a cancelled run does the work of at most one more chunk.
+///
+/// # Returns
+///
+/// The first error returned by `done`, or `Error::Cancelled` if `cancel` was set while a
+/// chunk was being mapped.
+pub(crate) fn for_each_chunked<S, T, R, F, D>(
+    items: &[T],
+    state: &mut S,
+    cancel: Option<&CancellationToken>,
+    f: F,
+    mut done: D,
+) -> Result<()>
+where
+    S: Sync,
+    T: Sync,
+    R: Send,
+    F: Fn(&S, &T) -> R + Sync + Send,
+    D: FnMut(&mut S, &T, R) -> Result<()>,
+{
+    let is_cancelled = || cancel.is_some_and(CancellationToken::is_cancelled);
+    for chunk in items.chunks(chunk_size()) {
+        let shared = &*state;
+        let results = map(chunk, |item| (!is_cancelled()).then(|| f(shared, item)));
+        for (item, result) in chunk.iter().zip(results) {
+            done(state, item, result.ok_or(Error::Cancelled)?)?;
+        }
+    }
+    Ok(())
+}
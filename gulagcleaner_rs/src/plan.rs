@@ -0,0 +1,202 @@
+use std::{collections::BTreeSet, error::Error, fmt::Display};
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+
+use crate::page_tree::delete_pages;
+use crate::parallel;
+use crate::progress::{uncancellable, Hooks, Stage};
+use crate::report::Warning;
+
+/// The page boxes that the cleaners rewrite.
+const PAGE_BOXES: [&str; 5] = ["MediaBox", "ArtBox", "TrimBox", "CropBox", "BleedBox"];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+/// The changes the cleaner will make to a single page.
+pub struct PagePlan {
+    /// The page number (1-based) in the original document.
+    pub page: u32,
+    /// The object ID of the page, checked before the plan is applied.
+    pub id: ObjectId,
+    /// New `/Contents` array, as references to content streams already in the document.
+    #[serde(default)]
+    pub contents: Option<Vec<ObjectId>>,
+    /// Transformation matrix the page contents are wrapped in, as in `q a b c d e f cm ... Q`.
+    #[serde(default)]
+    pub content_transform: Option<[f32; 6]>,
+    /// New MediaBox, ArtBox, TrimBox, CropBox and BleedBox of the page.
+    #[serde(default)]
+    pub boxes: Option<[f32; 4]>,
+    /// Whether to remove every annotation (the ad links) from the page.
+    #[serde(default)]
+    pub clear_annotations: bool,
+    /// Image XObjects to drop from the page. They are hidden by setting their height to zero,
+    /// so the content streams that draw them stay valid.
+    #[serde(default)]
+    pub drop_xobjects: Vec<ObjectId>,
+}
+
+impl PagePlan {
+    pub fn new(page: u32, id: ObjectId) -> PagePlan {
+        PagePlan {
+            page,
+            id,
+            ..Default::default()
+        }
+    }
+
+    /// The content of the page wrapped in `content_transform`, if the plan has one.
+    fn transformed_content(&self, doc: &Document) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        let Some(matrix) = self.content_transform else {
+            return Ok(None);
+        };
+        let mut contents = doc.get_page_content(self.id)?;
+        let matrix: Vec<String> = matrix.iter().map(|x| x.to_string()).collect();
+
+        let mut new_contents = Vec::new();
+        let c_prepend = format!("q\n{} cm\n", matrix.join(" "));
+        let c_append = "Q".as_bytes();
+
+        new_contents.extend_from_slice(c_prepend.as_bytes());
+        new_contents.append(&mut contents);
+        new_contents.extend_from_slice(c_append);
+        Ok(Some(new_contents))
+    }
+
+    /// Applies the plan to the page, with the content computed by `transformed_content`.
+    fn apply(&self, doc: &mut Document, content: Option<Vec<u8>>) -> Result<(), Box<dyn Error>> {
+        if let Some(content) = content {
+            doc.change_page_content(self.id, content)?;
+        }
+
+        let mutable_page = doc.get_dictionary_mut(self.id)?;
+        if let Some(contents) = &self.contents {
+            set_contents(mutable_page, contents);
+        }
+        if let Some(rect) = self.boxes {
+            set_boxes(mutable_page, rect);
+        }
+        if self.clear_annotations {
+            mutable_page.set("Annots", Object::Array(vec![]));
+        }
+
+        for xobject in &self.drop_xobjects {
+            let mutable_image = &mut doc.get_object_mut(*xobject)?.as_stream_mut()?.dict;
+            mutable_image.set(*b"Height", 0);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+/// Everything the cleaner decided to do to a document, before any change is made.
+///
+/// A plan can be serialised, reviewed or edited, and applied later to the same document
+/// or to an identical download of it.
+pub struct CleanPlan {
+    /// The cleaning method used: 0 for "Wuolah", 1 for "StuDocu", and 2 for "Naive".
+    pub method_code: u8,
+    /// Page numbers (1-based, in the original document) to delete.
+    pub delete: Vec<u32>,
+    /// The changes to make to the kept pages.
+    pub pages: Vec<PagePlan>,
+    /// Pages that could not be planned and will be left untouched.
+    #[serde(default)]
+    pub warnings: Vec<Warning>,
+}
+
+impl CleanPlan {
+    pub(crate) fn skip_page(&mut self, page: u32, reason: impl Display) {
+        self.warnings.push(Warning::PageSkipped {
+            page,
+            reason: reason.to_string(),
+        });
+    }
+
+    /// The pages that will actually be deleted from a document with `page_count` pages:
+    /// sorted, without duplicates and within range.
+    pub fn deleted_pages(&self, page_count: u32) -> Vec<u32> {
+        self.delete
+            .iter()
+            .copied()
+            .filter(|page| (1..=page_count).contains(page))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Applies the plan to a document.
+    ///
+    /// A page whose changes can't be applied, or whose object ID doesn't match the plan, is
+    /// left as it is and reported as a `Warning`. So is a page to delete that the document
+    /// doesn't have.
+    ///
+    /// # Returns
+    ///
+    /// The warnings raised while applying the plan.
+    pub fn apply(&self, doc: &mut Document) -> Vec<Warning> {
+        uncancellable(self.apply_with_hooks(doc, &Hooks::default()))
+    }
+
+    pub(crate) fn apply_with_hooks(
+        &self,
+        doc: &mut Document,
+        hooks: &Hooks,
+    ) -> crate::Result<Vec<Warning>> {
+        let mut warnings = Vec::new();
+        let pages = doc.get_pages();
+
+        // The new contents of a chunk of pages are computed independently from the document,
+        // then those pages are changed one by one.
+        parallel::for_each_chunked(
+            &self.pages,
+            doc,
+            hooks.cancel,
+            |doc, page_plan| match pages.get(&page_plan.page) {
+                Some(id) if *id == page_plan.id => page_plan
+                    .transformed_content(doc)
+                    .map_err(|e| e.to_string()),
+                _ => Err("the page object does not match the plan".to_string()),
+            },
+            |doc, page_plan, content| {
+                hooks.page(Stage::Applying, page_plan.page, pages.len() as u32)?;
+                let result = content
+                    .and_then(|content| page_plan.apply(doc, content).map_err(|e| e.to_string()));
+                if let Err(reason) = result {
+                    warnings.push(Warning::PageSkipped {
+                        page: page_plan.page,
+                        reason,
+                    });
+                }
+                Ok(())
+            },
+        )?;
+
+        //Delete the pages that we've marked for deletion.
+        let deletion = delete_pages(doc, &self.delete);
+        warnings.extend(
+            deletion
+                .out_of_range
+                .into_iter()
+                .map(|page| Warning::PageOutOfRange {
+                    page,
+                    page_count: pages.len() as u32,
+                }),
+        );
+        Ok(warnings)
+    }
+}
+
+fn set_contents(page: &mut Dictionary, contents: &[ObjectId]) {
+    let contents_objects: Vec<Object> = contents.iter().map(|x| Object::Reference(*x)).collect();
+    page.set(*b"Contents", Object::Array(contents_objects));
+}
+
+fn set_boxes(page: &mut Dictionary, rect: [f32; 4]) {
+    for _box in PAGE_BOXES {
+        page.set(
+            _box,
+            Object::Array(rect.iter().map(|x| Object::Real(*x)).collect()),
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// The stages of a cleaning run that report their progress page by page.
+pub enum Stage {
+    /// Deciding what to do with each page.
+    Classifying,
+    /// Applying the plan to each page.
+    Applying,
+    /// Checking each page of the cleaned document.
+    Verifying,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stage::Classifying => write!(f, "classifying"),
+            Stage::Applying => write!(f, "applying"),
+            Stage::Verifying => write!(f, "verifying"),
+        }
+    }
+}
+
+/// Receives progress updates while a document is cleaned.
+///
+/// It is implemented for any `Fn(Stage, u32, u32)`, so a closure can be passed directly.
+pub trait Progress {
+    /// Called when `page` (1-based) of `total` is processed in `stage`.
+    ///
+    /// With the `parallel` feature, the Naive pages are worked on in chunks, and each page is
+    /// reported once its chunk is finished.
+    fn page(&self, stage: Stage, page: u32, total: u32);
+}
+
+impl<F: Fn(Stage, u32, u32)> Progress for F {
+    fn page(&self, stage: Stage, page: u32, total: u32) {
+        self(stage, page, total)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// A flag that asks a running clean to stop.
+///
+/// Clones share the same flag. Cancellation is cooperative: it is checked between pages, and
+/// the run then fails with `Error::Cancelled` without finishing the document.
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Asks every run using this token to stop.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+/// The progress callback and cancellation token of a cleaning run. Both are optional.
+pub struct Hooks<'a> {
+    pub progress: Option<&'a dyn Progress>,
+    pub cancel: Option<&'a CancellationToken>,
+}
+
+impl Hooks<'_> {
+    /// Fails with `Error::Cancelled` if the run was cancelled.
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.cancel.is_some_and(CancellationToken::is_cancelled) {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Reports that `page` of `total` is about to be processed, after checking for cancellation.
+    pub(crate) fn page(&self, stage: Stage, page: u32, total: u32) -> Result<()> {
+        self.check()?;
+        if let Some(progress) = self.progress {
+            progress.page(stage, page, total);
+        }
+        Ok(())
+    }
+}
+
+/// Unwraps the result of a stage run without a cancellation token, which can't fail.
+pub(crate) fn uncancellable<T>(result: Result<T>) -> T {
+    result.unwrap_or_else(|e| unreachable!("a run without a cancellation token failed: {}", e))
+}
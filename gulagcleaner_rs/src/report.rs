@@ -0,0 +1,80 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::method::method_name;
+use crate::text::TextComparison;
+use crate::verify::{Verification, VerifyStatus};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+/// A non-fatal problem noticed while cleaning a document.
+pub enum Warning {
+    /// A page could not be processed and was left untouched.
+    PageSkipped { page: u32, reason: String },
+    /// A page has no images, so the Naive method can't tell whether it has ads and left it
+    /// untouched.
+    PageWithoutImages { page: u32 },
+    /// The plan deletes a page that the document doesn't have.
+    PageOutOfRange { page: u32, page_count: u32 },
+    /// More non-ad text disappeared from the kept pages than the threshold allows.
+    TextLoss { lost_ratio: f32, threshold: f32 },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::PageSkipped { page, reason } => {
+                write!(f, "page {} was left untouched: {}", page, reason)
+            }
+            Warning::PageWithoutImages { page } => {
+                write!(f, "page {} has no images and was left untouched", page)
+            }
+            Warning::PageOutOfRange { page, page_count } => write!(
+                f,
+                "page {} could not be deleted: the document has {} pages",
+                page, page_count
+            ),
+            Warning::TextLoss {
+                lost_ratio,
+                threshold,
+            } => write!(
+                f,
+                "{:.1}% of the text was lost (threshold {:.1}%)",
+                lost_ratio * 100.0,
+                threshold * 100.0
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// Summary of a cleaning run, returned alongside the cleaned document.
+pub struct CleanReport {
+    /// The cleaning method used: 0 for "Wuolah", 1 for "StuDocu", and 2 for "Naive".
+    pub method_code: u8,
+    /// Number of pages in the original document.
+    pub pages_before: u32,
+    /// Number of pages the cleaning plan leaves in the document.
+    pub pages_after: u32,
+    /// Page numbers (1-based, in the original document) removed by the cleaner.
+    pub deleted_pages: Vec<u32>,
+    /// Result of the post-clean verification pass.
+    pub verification: Verification,
+    /// Comparison of the text of the kept pages before and after cleaning.
+    pub text: TextComparison,
+    /// Non-fatal problems noticed while cleaning.
+    pub warnings: Vec<Warning>,
+}
+
+impl CleanReport {
+    /// The name of the cleaning method used: "Wuolah", "StuDocu" or "Naive".
+    pub fn method_name(&self) -> &'static str {
+        method_name(self.method_code)
+    }
+
+    /// The overall status of the cleaned document.
+    pub fn status(&self) -> VerifyStatus {
+        self.verification.status
+    }
+}
@@ -0,0 +1,307 @@
+//! Property tests that cleaning returns an error or a valid PDF, and never panics.
+//!
+//! Each case takes a synthetic download (or the StuDocu example), breaks its structure in a
+//! few of the ways real files do, and cleans it with and without forcing the Naive method,
+//! both fully and with `clean_lazy`.
+//! Set `PROPTEST_CASES` to run more than the default 64 cases.
+
+use std::sync::OnceLock;
+
+use lopdf::{Document, Object, ObjectId};
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+use crate::clean::{clean_pdf_with_report, CleanOptions};
+use crate::fixtures;
+use crate::lazy::clean_lazy;
+use crate::models::page_type::PageType;
+
+/// Keys removed from, or broken in, a page dictionary.
+const PAGE_KEYS: [&str; 6] = [
+    "MediaBox",
+    "Resources",
+    "Contents",
+    "Annots",
+    "Parent",
+    "Type",
+];
+
+/// Keys removed from the resources of a page.
+const RESOURCE_KEYS: [&str; 2] = ["XObject", "Font"];
+
+/// Keys removed from an image XObject.
+const IMAGE_KEYS: [&str; 3] = ["Width", "Height", "Subtype"];
+
+#[derive(Debug, Clone)]
+enum Base {
+    Wuolah(u32),
+    Wuolah010624(u32),
+    StuDocu(u32),
+    Naive(Vec<PageType>),
+    Example,
+}
+
+impl Base {
+    fn build(&self) -> Document {
+        match self {
+            Base::Wuolah(pages) => fixtures::wuolah(*pages),
+            Base::Wuolah010624(pages) => fixtures::wuolah_010624(*pages),
+            Base::StuDocu(pages) => fixtures::studocu(*pages),
+            Base::Naive(pages) => fixtures::naive(pages),
+            Base::Example => {
+                static EXAMPLE: OnceLock<Document> = OnceLock::new();
+                EXAMPLE
+                    .get_or_init(|| Document::load("example_docs/studocu-example.pdf").unwrap())
+                    .clone()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Mutation {
+    /// Removes a key from a page.
+    DropPageKey(Index, &'static str),
+    /// Removes a key from the resources of a page.
+    DropResource(Index, &'static str),
+    /// Moves a direct value of a page into its own object.
+    MakeIndirect(Index, &'static str),
+    /// Moves the numbers of the MediaBox of a page into their own objects.
+    IndirectBoxEntries(Index),
+    /// Moves the width and height of an image into their own objects.
+    IndirectImageSize(Index),
+    /// Makes a value of a page a reference to an integer.
+    WrongType(Index, &'static str),
+    /// Keeps only the first bytes of a stream.
+    TruncateStream(Index, usize),
+    /// Writes the boxes of a page with real numbers, or with integers.
+    Boxes(Index, bool),
+    /// Replaces the contents of a page with an empty array.
+    EmptyContents(Index),
+    /// Removes a key from an image.
+    DropImageKey(Index, &'static str),
+}
+
+impl Mutation {
+    fn apply(&self, doc: &mut Document) {
+        let pages: Vec<ObjectId> = doc.page_iter().collect();
+        if pages.is_empty() {
+            return;
+        }
+        let page = |index: &Index| index.get(&pages).to_owned();
+        match self {
+            Mutation::DropPageKey(index, key) => {
+                if let Ok(dict) = doc.get_dictionary_mut(page(index)) {
+                    dict.remove(key.as_bytes());
+                }
+            }
+            Mutation::DropResource(index, key) => {
+                let (direct, inherited) = doc.get_page_resources(page(index));
+                let id = match (direct.is_some(), inherited.first()) {
+                    (true, _) => page(index),
+                    (false, Some(id)) => *id,
+                    (false, None) => return,
+                };
+                let resources = match doc.get_dictionary_mut(id) {
+                    Ok(dict) if id == page(index) => dict
+                        .get_mut(b"Resources")
+                        .and_then(Object::as_dict_mut)
+                        .ok(),
+                    Ok(dict) => Some(dict),
+                    Err(_) => None,
+                };
+                if let Some(resources) = resources {
+                    resources.remove(key.as_bytes());
+                }
+            }
+            Mutation::MakeIndirect(index, key) => {
+                let value = match doc
+                    .get_dictionary(page(index))
+                    .and_then(|x| x.get(key.as_bytes()))
+                {
+                    Ok(Object::Reference(_)) | Err(_) => return,
+                    Ok(value) => value.clone(),
+                };
+                let id = doc.add_object(value);
+                set_page_key(doc, page(index), key, Object::Reference(id));
+            }
+            Mutation::IndirectBoxEntries(index) => {
+                let Ok(Object::Array(rect)) = doc
+                    .get_dictionary(page(index))
+                    .and_then(|x| x.get(b"MediaBox"))
+                    .cloned()
+                else {
+                    return;
+                };
+                let rect: Vec<Object> = rect
+                    .into_iter()
+                    .map(|x| Object::Reference(doc.add_object(x)))
+                    .collect();
+                set_page_key(doc, page(index), "MediaBox", Object::Array(rect));
+            }
+            Mutation::IndirectImageSize(index) => {
+                let images = images(doc);
+                if images.is_empty() {
+                    return;
+                }
+                let id = *index.get(&images);
+                for key in ["Width", "Height"] {
+                    let value = doc
+                        .get_object(id)
+                        .and_then(Object::as_stream)
+                        .and_then(|x| x.dict.get(key.as_bytes()))
+                        .cloned();
+                    if let Ok(value) = value {
+                        let value = doc.add_object(value);
+                        if let Ok(stream) = doc.get_object_mut(id).and_then(Object::as_stream_mut) {
+                            stream.dict.set(key, value);
+                        }
+                    }
+                }
+            }
+            Mutation::WrongType(index, key) => {
+                let id = doc.add_object(Object::Integer(7));
+                set_page_key(doc, page(index), key, Object::Reference(id));
+            }
+            Mutation::TruncateStream(index, len) => {
+                let streams: Vec<ObjectId> = doc
+                    .objects
+                    .iter()
+                    .filter(|(_, x)| x.as_stream().is_ok())
+                    .map(|(id, _)| *id)
+                    .collect();
+                if streams.is_empty() {
+                    return;
+                }
+                let id = *index.get(&streams);
+                if let Ok(stream) = doc.get_object_mut(id).and_then(Object::as_stream_mut) {
+                    let len = (*len).min(stream.content.len());
+                    stream.content.truncate(len);
+                }
+            }
+            Mutation::Boxes(index, real) => {
+                let Ok(dict) = doc.get_dictionary_mut(page(index)) else {
+                    return;
+                };
+                let rect: Vec<Object> = [0.0, 0.0, 595.0, 842.0]
+                    .iter()
+                    .map(|x: &f32| {
+                        if *real {
+                            Object::Real(*x)
+                        } else {
+                            Object::Integer(*x as i64)
+                        }
+                    })
+                    .collect();
+                for key in ["MediaBox", "CropBox"] {
+                    dict.set(key, rect.clone());
+                }
+            }
+            Mutation::EmptyContents(index) => {
+                set_page_key(doc, page(index), "Contents", Object::Array(vec![]));
+            }
+            Mutation::DropImageKey(index, key) => {
+                let images = images(doc);
+                if images.is_empty() {
+                    return;
+                }
+                let id = *index.get(&images);
+                if let Ok(stream) = doc.get_object_mut(id).and_then(Object::as_stream_mut) {
+                    stream.dict.remove(key.as_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// The streams that look like images.
+fn images(doc: &Document) -> Vec<ObjectId> {
+    doc.objects
+        .iter()
+        .filter(|(_, x)| {
+            x.as_stream()
+                .is_ok_and(|x| x.dict.has(b"Width") || x.dict.has(b"Height"))
+        })
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+fn set_page_key(doc: &mut Document, page: ObjectId, key: &str, value: Object) {
+    if let Ok(dict) = doc.get_dictionary_mut(page) {
+        dict.set(key, value);
+    }
+}
+
+/// Proptest's default configuration, which reads the `PROPTEST_*` variables, running 64
+/// cases unless `PROPTEST_CASES` is set.
+fn config() -> ProptestConfig {
+    let mut config = ProptestConfig::default();
+    if std::env::var_os("PROPTEST_CASES").is_none() {
+        config.cases = 64;
+    }
+    config
+}
+
+fn base() -> impl Strategy<Value = Base> {
+    let page_type = prop_oneof![
+        Just(PageType::BannerAds),
+        Just(PageType::FullPageAds),
+        Just(PageType::Watermark),
+        Just(PageType::Idk),
+    ];
+    prop_oneof![
+        3 => (2..5u32).prop_map(Base::Wuolah),
+        3 => (2..5u32).prop_map(Base::Wuolah010624),
+        3 => (1..4u32).prop_map(Base::StuDocu),
+        3 => prop::collection::vec(page_type, 1..6).prop_map(Base::Naive),
+        1 => Just(Base::Example),
+    ]
+}
+
+fn mutation() -> impl Strategy<Value = Mutation> {
+    let page_key = prop::sample::select(&PAGE_KEYS[..]);
+    prop_oneof![
+        (any::<Index>(), page_key.clone()).prop_map(|(i, k)| Mutation::DropPageKey(i, k)),
+        (any::<Index>(), prop::sample::select(&RESOURCE_KEYS[..]))
+            .prop_map(|(i, k)| Mutation::DropResource(i, k)),
+        (any::<Index>(), page_key.clone()).prop_map(|(i, k)| Mutation::MakeIndirect(i, k)),
+        (any::<Index>(), page_key).prop_map(|(i, k)| Mutation::WrongType(i, k)),
+        (any::<Index>(), 0..64usize).prop_map(|(i, len)| Mutation::TruncateStream(i, len)),
+        (any::<Index>(), any::<bool>()).prop_map(|(i, real)| Mutation::Boxes(i, real)),
+        any::<Index>().prop_map(Mutation::EmptyContents),
+        any::<Index>().prop_map(Mutation::IndirectBoxEntries),
+        any::<Index>().prop_map(Mutation::IndirectImageSize),
+        (any::<Index>(), prop::sample::select(&IMAGE_KEYS[..]))
+            .prop_map(|(i, k)| Mutation::DropImageKey(i, k)),
+    ]
+}
+
+proptest! {
+    #![proptest_config(config())]
+
+    #[test]
+    fn clean_never_panics(
+        base in base(),
+        mutations in prop::collection::vec(mutation(), 1..4),
+    ) {
+        let mut doc = base.build();
+        for mutation in &mutations {
+            mutation.apply(&mut doc);
+        }
+        let data = fixtures::to_bytes(&mut doc);
+
+        for force_naive in [false, true] {
+            let options = CleanOptions {
+                force_naive,
+                ..Default::default()
+            };
+            if let Ok((clean_pdf, _)) = clean_pdf_with_report(&data, &options) {
+                prop_assert!(Document::load_mem(&clean_pdf).is_ok());
+            }
+            let mut clean_pdf = Vec::new();
+            if clean_lazy(&data, &mut clean_pdf, &options).is_ok() {
+                prop_assert!(Document::load_mem(&clean_pdf).is_ok());
+            }
+        }
+    }
+}
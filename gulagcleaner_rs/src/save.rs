@@ -0,0 +1,89 @@
+use crate::clean::CleanOptions;
+use crate::error::{Error, Result};
+
+use lopdf::{Document, Object, StringFormat};
+use md5::{Digest, Md5};
+use time::OffsetDateTime;
+
+/// Length of a file identifier, the size of an MD5 digest.
+const ID_LEN: usize = 16;
+
+/// Serialises a cleaned document so that the same input and options always give the same
+/// bytes.
+///
+/// Objects are renumbered from 1 in the order lopdf stores them, and both halves of the
+/// trailer `/ID` are set to the MD5 digest of the output, computed with a zeroed `/ID`. The
+/// document's `/ModDate` is only changed when `options.mod_date` is set.
+///
+/// # Arguments
+///
+/// * `doc` - The document to save. It is renumbered in place.
+/// * `options` - The options the document was cleaned with.
+///
+/// # Returns
+///
+/// The PDF data, `Error::Save` if the document could not be written, or
+/// `Error::InvalidOptions` if `options.mod_date` is out of range.
+pub fn save_document(doc: &mut Document, options: &CleanOptions) -> Result<Vec<u8>> {
+    if let Some(timestamp) = options.mod_date {
+        set_mod_date(doc, timestamp)?;
+    }
+    doc.renumber_objects();
+
+    let placeholder = Object::String(vec![0; ID_LEN], StringFormat::Hexadecimal);
+    doc.trailer
+        .set("ID", vec![placeholder.clone(), placeholder]);
+    let mut data = Vec::new();
+    doc.save_to(&mut data).map_err(Error::Save)?;
+
+    let id = Md5::digest(&data);
+    let id_hex = format!(
+        "<{}>",
+        id.iter().map(|x| format!("{:02X}", x)).collect::<String>()
+    );
+    // The trailer is written last, so its two placeholders are the last ones in the file.
+    let placeholder = format!("<{}>", "0".repeat(ID_LEN * 2));
+    let mut end = data.len();
+    for _ in 0..2 {
+        let Some(at) = data[..end]
+            .windows(placeholder.len())
+            .rposition(|x| x == placeholder.as_bytes())
+        else {
+            break;
+        };
+        data[at..at + placeholder.len()].copy_from_slice(id_hex.as_bytes());
+        end = at;
+    }
+
+    let id = Object::String(id.to_vec(), StringFormat::Hexadecimal);
+    doc.trailer.set("ID", vec![id.clone(), id]);
+    Ok(data)
+}
+
+/// Converts the `mod_date` option to a date.
+///
+/// # Returns
+///
+/// The date, or `Error::InvalidOptions` if the timestamp is out of the supported range.
+pub(crate) fn mod_date_time(timestamp: i64) -> Result<OffsetDateTime> {
+    OffsetDateTime::from_unix_timestamp(timestamp)
+        .map_err(|e| Error::InvalidOptions(format!("mod_date {}: {}", timestamp, e)))
+}
+
+/// Sets `/ModDate` in the document information dictionary, creating it if needed.
+pub(crate) fn set_mod_date(doc: &mut Document, timestamp: i64) -> Result<()> {
+    let date = mod_date_time(timestamp)?;
+
+    let info = match doc.trailer.get(b"Info").and_then(Object::as_reference) {
+        Ok(id) if doc.get_dictionary(id).is_ok() => id,
+        _ => {
+            let id = doc.add_object(lopdf::Dictionary::new());
+            doc.trailer.set("Info", id);
+            id
+        }
+    };
+    if let Ok(info) = doc.get_dictionary_mut(info) {
+        info.set("ModDate", Object::from(date));
+    }
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeSet;
+
+use lopdf::{
+    content::{Content, Operation},
+    Document, Object, ObjectId, Stream,
+};
+
+use crate::clean::{load_document, CleanOptions};
+use crate::error::Result;
+use crate::save::save_document;
+
+/// Byte every string is overwritten with.
+const BLANK: u8 = b'x';
+
+/// Builds an anonymized copy of a document that can be attached to a bug report.
+///
+/// The copy keeps the object numbers, the page tree, every dictionary (so XObjects keep
+/// their `/Width` and `/Height`) and the operators of the content streams, which is all
+/// that the method and page type detection look at. Every string is replaced by as many
+/// `x` bytes, in the objects and inside the content streams, and every other stream
+/// (images, fonts, metadata) is emptied.
+///
+/// # Arguments
+///
+/// * `doc` - The document to anonymize. It is not modified.
+///
+/// # Returns
+///
+/// The anonymized document.
+pub fn export_skeleton(doc: &Document) -> Document {
+    let mut skeleton = doc.clone();
+    let content_streams: BTreeSet<ObjectId> = skeleton
+        .page_iter()
+        .flat_map(|page| skeleton.get_page_contents(page))
+        .collect();
+
+    for (id, object) in skeleton.objects.iter_mut() {
+        blank_strings(object);
+        if let Object::Stream(stream) = object {
+            let is_form = stream
+                .dict
+                .get(b"Subtype")
+                .and_then(Object::as_name)
+                .is_ok_and(|x| x == b"Form");
+            let content = if content_streams.contains(id) || is_form {
+                blank_content(stream)
+            } else {
+                Vec::new()
+            };
+            stream.set_plain_content(content);
+        }
+    }
+    for (_, value) in skeleton.trailer.iter_mut() {
+        blank_strings(value);
+    }
+    skeleton
+}
+
+/// Builds the anonymized skeleton of PDF document data, as [`export_skeleton`] does.
+///
+/// # Arguments
+///
+/// * `data` - The PDF document data.
+///
+/// # Returns
+///
+/// The skeleton's PDF data, or an error if the document could not be loaded or saved.
+pub fn export_skeleton_pdf(data: &[u8]) -> Result<Vec<u8>> {
+    let doc = load_document(data)?;
+    save_document(&mut export_skeleton(&doc), &CleanOptions::default())
+}
+
+/// Replaces every string inside `object` with as many `x` bytes.
+fn blank_strings(object: &mut Object) {
+    match object {
+        Object::String(text, _) => text.fill(BLANK),
+        Object::Array(items) => items.iter_mut().for_each(blank_strings),
+        Object::Dictionary(dict) => dict.iter_mut().for_each(|(_, x)| blank_strings(x)),
+        Object::Stream(stream) => stream.dict.iter_mut().for_each(|(_, x)| blank_strings(x)),
+        _ => {}
+    }
+}
+
+/// Decodes a content stream and encodes it again with its string operands blanked.
+///
+/// Streams that can't be decoded, such as those with inline images, become empty.
+fn blank_content(stream: &Stream) -> Vec<u8> {
+    let data = if stream.filters().is_ok_and(|x| x.is_empty()) {
+        Ok(stream.content.clone())
+    } else {
+        stream.decompressed_content()
+    };
+    let Ok(mut content) = data.and_then(|x| Content::decode(&x)) else {
+        return Vec::new();
+    };
+    content
+        .operations
+        .iter_mut()
+        .flat_map(|x: &mut Operation| x.operands.iter_mut())
+        .for_each(blank_strings);
+    content.encode().unwrap_or_default()
+}
@@ -0,0 +1,181 @@
+//! Structural snapshots of cleaned documents.
+//!
+//! Each test cleans a document and compares a summary of the output (page boxes, content
+//! references, XObject names and annotation counts) with the JSON file checked in under
+//! `example_docs/snapshots`, so any change in what the cleaner does shows up as a diff.
+//! Run the tests with `UPDATE_SNAPSHOTS=1` to write the new summaries after an intended
+//! change.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use lopdf::{Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+
+use crate::clean::{clean_pdf_with_report, CleanOptions};
+use crate::fixtures;
+use crate::models::method::{get_xobjs, method_name};
+use crate::models::page_type::PageType;
+
+const SNAPSHOT_PATH: &str = "example_docs/snapshots";
+
+/// The page boxes included in a summary, when the page defines them.
+const PAGE_BOXES: [&str; 5] = ["MediaBox", "CropBox", "BleedBox", "TrimBox", "ArtBox"];
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct DocumentSummary {
+    method: String,
+    deleted_pages: Vec<u32>,
+    page_count: usize,
+    pages: Vec<PageSummary>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct PageSummary {
+    page: u32,
+    /// Each box as `llx lly urx ury`.
+    boxes: BTreeMap<String, String>,
+    contents: Vec<String>,
+    xobjects: Vec<String>,
+    annotations: usize,
+}
+
+/// Summarises a cleaned document, as it was saved.
+fn summarize(clean_pdf: &[u8], method_code: u8, deleted_pages: Vec<u32>) -> DocumentSummary {
+    let doc = Document::load_mem(clean_pdf).expect("the cleaned document parses");
+    let pages: Vec<PageSummary> = doc
+        .get_pages()
+        .into_iter()
+        .map(|(page, id)| summarize_page(&doc, page, id))
+        .collect();
+    DocumentSummary {
+        method: method_name(method_code).to_string(),
+        deleted_pages,
+        page_count: pages.len(),
+        pages,
+    }
+}
+
+fn summarize_page(doc: &Document, page: u32, id: ObjectId) -> PageSummary {
+    let dict = doc.get_dictionary(id).expect("pages are dictionaries");
+
+    let mut boxes = BTreeMap::new();
+    for name in PAGE_BOXES {
+        let Ok((_, Object::Array(rect))) =
+            dict.get(name.as_bytes()).and_then(|x| doc.dereference(x))
+        else {
+            continue;
+        };
+        let rect: Vec<String> = rect
+            .iter()
+            .map(|x| match doc.dereference(x) {
+                Ok((_, Object::Integer(x))) => x.to_string(),
+                Ok((_, Object::Real(x))) => x.to_string(),
+                _ => "?".to_string(),
+            })
+            .collect();
+        boxes.insert(name.to_string(), rect.join(" "));
+    }
+
+    let contents = doc
+        .get_page_contents(id)
+        .into_iter()
+        .map(|(number, generation)| format!("{} {} R", number, generation))
+        .collect();
+
+    let xobjects = match get_xobjs(doc, &id) {
+        Ok(Some(xobjs)) => xobjs
+            .iter()
+            .map(|(name, _)| String::from_utf8_lossy(name).into_owned())
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let annotations = match dict.get(b"Annots").and_then(|x| doc.dereference(x)) {
+        Ok((_, Object::Array(annots))) => annots.len(),
+        _ => 0,
+    };
+
+    PageSummary {
+        page,
+        boxes,
+        contents,
+        xobjects,
+        annotations,
+    }
+}
+
+/// Cleans `data` and compares the summary of the output with the snapshot `name`.
+///
+/// When `UPDATE_SNAPSHOTS` is set the snapshot is written instead. A missing snapshot is
+/// written too, but the test fails so that it gets reviewed and checked in.
+fn check_snapshot(name: &str, data: &[u8], options: &CleanOptions) {
+    let (clean_pdf, report) = clean_pdf_with_report(data, options).expect("the document cleans");
+    let summary = summarize(&clean_pdf, report.method_code, report.deleted_pages);
+    let actual = serde_json::to_string_pretty(&summary).unwrap() + "\n";
+
+    let path = format!("{}/{}.json", SNAPSHOT_PATH, name);
+    let expected = fs::read_to_string(&path).ok();
+    if expected.is_none() || std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(SNAPSHOT_PATH).expect("Failed to create snapshot directory");
+        fs::write(&path, &actual).expect("Failed to write snapshot");
+        assert!(
+            expected.is_some(),
+            "`{}` did not exist and has been written, review it and run the tests again",
+            path
+        );
+        return;
+    }
+
+    let expected = expected.unwrap_or_default();
+    let expected_summary: DocumentSummary = serde_json::from_str(&expected)
+        .unwrap_or_else(|e| panic!("`{}` is not a valid snapshot: {}", path, e));
+    assert!(
+        expected_summary == summary,
+        "the output no longer matches `{}`. If the change is intended, run the tests with \
+         UPDATE_SNAPSHOTS=1 and review the diff.\nexpected:\n{}\nactual:\n{}",
+        path,
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn snapshot_studocu_example() {
+    let data = fs::read("example_docs/studocu-example.pdf").expect("Failed to read PDF");
+    check_snapshot("studocu-example", &data, &CleanOptions::default());
+}
+
+#[test]
+fn snapshot_wuolah_fixture() {
+    let data = fixtures::to_bytes(&mut fixtures::wuolah(3));
+    check_snapshot("wuolah-fixture", &data, &CleanOptions::default());
+}
+
+#[test]
+fn snapshot_wuolah_010624_fixture() {
+    let data = fixtures::to_bytes(&mut fixtures::wuolah_010624(3));
+    check_snapshot("wuolah-010624-fixture", &data, &CleanOptions::default());
+}
+
+#[test]
+fn snapshot_studocu_fixture() {
+    let data = fixtures::to_bytes(&mut fixtures::studocu(3));
+    check_snapshot("studocu-fixture", &data, &CleanOptions::default());
+}
+
+#[test]
+fn snapshot_naive_fixture() {
+    let mut doc = fixtures::naive(&[
+        PageType::FullPageAds,
+        PageType::BannerAds,
+        PageType::Watermark,
+        PageType::BannerAds,
+        PageType::Idk,
+    ]);
+    check_snapshot(
+        "naive-fixture",
+        &fixtures::to_bytes(&mut doc),
+        &CleanOptions::default(),
+    );
+}
@@ -1,5 +1,30 @@
-use crate::clean::clean_pdf;
+use crate::analyze::{analyze_document, analyze_document_with_hooks};
+use crate::clean::{
+    apply_plan, clean_document, clean_pdf_with_hooks, clean_pdf_with_report, clean_reader,
+    load_document, load_document_with_limits, plan_document, CleanOptions,
+};
+use crate::error::Error;
+use crate::fixtures;
+use crate::index::PageIndex;
+use crate::lazy::{clean_lazy, write_object, LazyDocument};
+use crate::limits::{Limit, Limits};
+use crate::metadata::extract_metadata;
+use crate::models::method::{get_logos, get_mediabox};
+use crate::models::page_type::PageType;
+use crate::page_tree::delete_pages;
+use crate::parallel;
+use crate::plan::CleanPlan;
+use crate::progress::{CancellationToken, Hooks, Stage};
+use crate::report::Warning;
+use crate::skeleton::{export_skeleton, export_skeleton_pdf};
+use crate::text::{compare_text, extract_page_text, TEXT_LOSS_THRESHOLD};
+use crate::verify::{verify_document, verify_pdf, VerifyIssue, VerifyStatus};
+use lopdf::{dictionary, Dictionary, Document, Object, Stream};
+use md5::Digest;
+use std::cell::RefCell;
 use std::fs;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
 
 const OUT_PATH: &str = "example_docs/out";
@@ -30,7 +55,8 @@ fn create_output_directory() {
 fn read_and_clean_pdf(in_path: &str) -> Result<Vec<u8>, String> {
     let data =
         std::fs::read(in_path).map_err(|e| format!("Failed to read `{}`: {}", in_path, e))?;
-    let (clean_file, _) = clean_pdf(data, false);
+    let (clean_file, _) = clean_pdf_with_report(&data, &CleanOptions::default())
+        .map_err(|e| format!("Failed to clean `{}`: {}", in_path, e))?;
     Ok(clean_file)
 }
 
@@ -81,20 +107,148 @@ fn run_test_for_config(test_config: &TestConfig) {
 
 // Define tests for specific PDF files, utilizing the TestConfig structure.
 
+/// Cleans a synthetic Wuolah download and checks the structure of the result.
+///
+/// # Arguments
+///
+/// * `doc` - A document built by `fixtures::wuolah` or `fixtures::wuolah_010624`.
+/// * `notes_pages` - The number of notes pages in `doc`.
+/// * `output_filename` - The name the cleaned PDF is stored with in the output directory.
+fn check_wuolah_fixture(mut doc: Document, notes_pages: u32, output_filename: &str) {
+    let pages = doc.get_pages();
+    let original_contents: Vec<Vec<(u32, u16)>> =
+        pages.values().map(|x| doc.get_page_contents(*x)).collect();
+
+    let report = clean_document(&mut doc, &CleanOptions::default()).unwrap();
+    assert_eq!(report.method_code, 0);
+    assert_eq!(report.deleted_pages, vec![1, notes_pages + 2]);
+    assert_eq!(report.status(), VerifyStatus::Passed, "{:?}", report);
+    assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+
+    for (i, page) in doc.page_iter().enumerate() {
+        // The banner and the footer are dropped, the notes and their wrappers are kept
+        assert_eq!(doc.get_page_contents(page), original_contents[i + 1][1..=7]);
+        let dict = doc.get_dictionary(page).unwrap();
+        assert!(dict.get(b"Annots").unwrap().as_array().unwrap().is_empty());
+        assert_eq!(get_mediabox(&doc, page).unwrap(), [0.0, 0.0, 595.0, 842.0]);
+        let text = extract_page_text(&doc, page).join(" ");
+        assert_eq!(text, fixtures::notes_text(i as u32 + 1));
+    }
+
+    create_output_directory();
+    store_pdf(
+        &format!("{}/{}", OUT_PATH, output_filename),
+        fixtures::to_bytes(&mut doc),
+    )
+    .expect("Failed to store PDF");
+}
+
 #[test]
 fn test_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-free-example.pdf",
-        output_filename: "wuolah_clean.pdf",
-    });
+    check_wuolah_fixture(fixtures::wuolah(4), 4, "wuolah_clean.pdf");
 }
 
 #[test]
 fn test_010624_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-010624-example.pdf",
-        output_filename: "wuolah_010624_clean.pdf",
-    });
+    check_wuolah_fixture(fixtures::wuolah_010624(4), 4, "wuolah_010624_clean.pdf");
+}
+
+#[test]
+fn test_studocu_fixture() {
+    let mut doc = fixtures::studocu(3);
+    let pages = doc.get_pages();
+    let notes_streams: Vec<(u32, u16)> = pages
+        .values()
+        .skip(1)
+        .map(|x| doc.get_page_contents(*x)[1])
+        .collect();
+
+    let data = fixtures::to_bytes(&mut doc);
+    let analysis = analyze_document(&load_document(&data).unwrap());
+    assert_eq!(analysis.method_code, 1);
+
+    let report = clean_document(&mut doc, &CleanOptions::default()).unwrap();
+    assert_eq!(report.deleted_pages, vec![1]);
+    assert_eq!(report.pages_after, 3);
+    assert_eq!(report.status(), VerifyStatus::Passed, "{:?}", report);
+    // Only the header and footer text is lost, and it is ad text
+    assert_eq!(report.text.words_lost, 0);
+    for (i, page) in doc.page_iter().enumerate() {
+        assert_eq!(doc.get_page_contents(page), vec![notes_streams[i]]);
+        let dict = doc.get_dictionary(page).unwrap();
+        assert!(dict.get(b"Annots").unwrap().as_array().unwrap().is_empty());
+    }
+}
+
+#[test]
+fn test_naive_fixture() {
+    let mut doc = fixtures::naive(&[
+        PageType::FullPageAds,
+        PageType::BannerAds,
+        PageType::Watermark,
+        PageType::Idk,
+    ]);
+    let pages = doc.get_pages();
+    for (page, expected) in [(2, PageType::BannerAds), (3, PageType::Watermark)] {
+        assert_eq!(
+            PageType::get_page_type(&doc, &pages[&page]).unwrap(),
+            expected
+        );
+    }
+    let logo = doc
+        .get_dictionary(pages[&3])
+        .and_then(|x| x.get_deref(b"Resources", &doc))
+        .and_then(Object::as_dict)
+        .and_then(|x| x.get(b"XObject"))
+        .and_then(Object::as_dict)
+        .and_then(|x| x.get(b"Logo"))
+        .and_then(Object::as_reference)
+        .unwrap();
+
+    // Detected as Naive without forcing it
+    let report = clean_document(&mut doc, &CleanOptions::default()).unwrap();
+    assert_eq!(report.method_code, 2);
+    assert_eq!(report.deleted_pages, vec![1, 4]);
+    assert_eq!(report.status(), VerifyStatus::Passed, "{:?}", report);
+
+    let pages = doc.get_pages();
+    let banner_page = doc.get_page_content(pages[&1]).unwrap();
+    assert!(banner_page.starts_with(b"q\n1.124 0 0 1.124 0 0 cm\n"));
+    let [x, y, width, height] = get_mediabox(&doc, pages[&1]).unwrap();
+    assert!(x > 0.0 && y > 0.0 && width < 595.0 * 1.124 && height < 842.0 * 1.124);
+    assert_ne!(
+        get_mediabox(&doc, pages[&2]).unwrap(),
+        [0.0, 0.0, 595.0, 842.0]
+    );
+    let logo = doc.get_object(logo).unwrap().as_stream().unwrap();
+    assert_eq!(logo.dict.get(b"Height").unwrap().as_i64().unwrap(), 0);
+    for page in pages.values() {
+        let dict = doc.get_dictionary(*page).unwrap();
+        assert!(dict.get(b"Annots").unwrap().as_array().unwrap().is_empty());
+    }
+}
+
+#[test]
+fn test_naive_keeps_pages_without_images() {
+    let mut doc = fixtures::text_only(2);
+    let options = CleanOptions {
+        force_naive: true,
+        ..Default::default()
+    };
+    let report = clean_document(&mut doc, &options).unwrap();
+    assert!(report.deleted_pages.is_empty());
+    assert_eq!(report.pages_after, 2);
+    assert_eq!(
+        report.warnings,
+        vec![
+            Warning::PageWithoutImages { page: 1 },
+            Warning::PageWithoutImages { page: 2 }
+        ]
+    );
+    for (i, page) in doc.page_iter().enumerate() {
+        let text = extract_page_text(&doc, page).join(" ");
+        assert_eq!(text, fixtures::notes_text(i as u32 + 1));
+    }
 }
 
 #[test]
@@ -104,3 +258,1152 @@ fn test_studocu_pdf() {
         output_filename: "studocu_clean.pdf",
     });
 }
+
+#[test]
+fn test_studocu_pdf_verifies() {
+    let data = fs::read("example_docs/studocu-example.pdf").expect("Failed to read PDF");
+    let pages_before = Document::load_mem(&data).unwrap().get_pages().len() as u32;
+
+    let (clean_file, report) = clean_pdf_with_report(&data, &CleanOptions::default()).unwrap();
+
+    assert_eq!(report.method_code, 1);
+    assert_eq!(report.pages_before, pages_before);
+    assert_eq!(report.deleted_pages, vec![1]);
+    assert_eq!(report.pages_after, pages_before - 1);
+    assert_eq!(
+        report.status(),
+        VerifyStatus::Passed,
+        "{:?}",
+        report.verification
+    );
+    assert_eq!(
+        verify_pdf(&clean_file, report.pages_after).status,
+        VerifyStatus::Passed
+    );
+}
+
+#[test]
+fn test_verify_fails_when_every_page_is_deleted() {
+    let data = fs::read("example_docs/studocu-example.pdf").unwrap();
+    // None of the images of the example are known ads, so Naive deletes every page
+    let options = CleanOptions {
+        force_naive: true,
+        ..Default::default()
+    };
+    let (_, report) = clean_pdf_with_report(&data, &options).unwrap();
+    assert_eq!(report.pages_after, 0);
+    assert_eq!(report.status(), VerifyStatus::Failed);
+    assert!(report
+        .verification
+        .issues
+        .contains(&VerifyIssue::NoPagesLeft));
+
+    let report = clean_lazy(&data, &mut Vec::new(), &options).unwrap();
+    assert_eq!(report.status(), VerifyStatus::Failed);
+    assert!(report
+        .verification
+        .issues
+        .contains(&VerifyIssue::NoPagesLeft));
+}
+
+#[test]
+fn test_verify_rejects_broken_output() {
+    let verification = verify_pdf(b"%PDF-1.5\nnot really a pdf", 1);
+    assert_eq!(verification.status, VerifyStatus::Failed);
+    assert!(matches!(
+        verification.issues[0],
+        VerifyIssue::Unparseable { .. }
+    ));
+
+    let data = fs::read("example_docs/studocu-example.pdf").expect("Failed to read PDF");
+    let verification = verify_pdf(&data, 1);
+    assert_eq!(verification.status, VerifyStatus::Failed);
+}
+
+#[test]
+fn test_verify_reports_missing_resources() {
+    let mut doc = Document::load("example_docs/studocu-example.pdf").unwrap();
+    let pages = doc.get_pages();
+    let page_id = pages[&2];
+    doc.get_dictionary_mut(page_id)
+        .unwrap()
+        .set("Resources", Dictionary::new());
+
+    let verification = verify_document(&doc, pages.len() as u32);
+    assert_eq!(verification.status, VerifyStatus::Degraded);
+    assert!(verification
+        .issues
+        .iter()
+        .any(|x| matches!(x, VerifyIssue::MissingResource { page: 2, .. })));
+}
+
+#[test]
+fn test_verify_ignores_malformed_annotations() {
+    let mut doc = fixtures::studocu(1);
+    let pages = doc.get_pages();
+    let not_an_array = doc.add_object(7);
+    doc.get_dictionary_mut(pages[&2])
+        .unwrap()
+        .set("Annots", not_an_array);
+
+    let verification = verify_document(&doc, 2);
+    assert!(!verification
+        .issues
+        .iter()
+        .any(|x| matches!(x, VerifyIssue::AdSignature { page: 2, .. })));
+}
+
+#[test]
+fn test_text_comparison_ignores_ad_text() {
+    let lines = |x: &[&str]| x.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    let before = vec![
+        lines(&[
+            "Descargado por alguien (alguien@example.com)",
+            "TEMA 1: Introducción",
+        ]),
+        lines(&[
+            "Reservados todos los derechos.",
+            "La célula es la unidad básica",
+        ]),
+    ];
+
+    let ads_removed = vec![
+        lines(&["TEMA 1: Introducción"]),
+        lines(&["La célula es la unidad básica"]),
+    ];
+    let comparison = compare_text(&before, &ads_removed);
+    assert_eq!(comparison.words_lost, 0);
+
+    let content_removed = vec![lines(&["TEMA 1: Introducción"]), vec![]];
+    let comparison = compare_text(&before, &content_removed);
+    assert_eq!(comparison.words_lost, 6);
+    assert!(comparison.lost_ratio() > TEXT_LOSS_THRESHOLD);
+}
+
+#[test]
+fn test_unprocessable_page_is_left_untouched() {
+    let mut doc = Document::load("example_docs/studocu-example.pdf").unwrap();
+    let pages = doc.get_pages();
+    doc.get_dictionary_mut(pages[&2])
+        .unwrap()
+        .remove(b"Resources");
+    let mut data = Vec::new();
+    doc.save_to(&mut data).unwrap();
+
+    let options = CleanOptions {
+        force_naive: true,
+        ..Default::default()
+    };
+    let (_, report) = clean_pdf_with_report(&data, &options).unwrap();
+
+    assert_eq!(report.method_code, 2);
+    assert!(!report.deleted_pages.contains(&2));
+    assert!(matches!(
+        report.warnings.as_slice(),
+        [Warning::PageSkipped { page: 2, .. }]
+    ));
+}
+
+#[test]
+fn test_get_mediabox() {
+    let mut doc = Document::load("example_docs/studocu-example.pdf").unwrap();
+    let pages = doc.get_pages();
+    assert_eq!(
+        get_mediabox(&doc, pages[&2]).unwrap(),
+        [0.0, 0.0, 595.0, 842.0]
+    );
+
+    // Inherited from the page tree
+    let page = doc.get_dictionary_mut(pages[&2]).unwrap();
+    page.remove(b"MediaBox");
+    let parent = page.get(b"Parent").unwrap().as_reference().unwrap();
+    doc.get_dictionary_mut(parent).unwrap().set(
+        "MediaBox",
+        vec![0.into(), 0.into(), Object::Real(612.0), 792.into()],
+    );
+    assert_eq!(
+        get_mediabox(&doc, pages[&2]).unwrap(),
+        [0.0, 0.0, 612.0, 792.0]
+    );
+
+    let page = doc.get_dictionary_mut(pages[&3]).unwrap();
+    page.set("MediaBox", vec![0.into(), 0.into(), "A4".into()]);
+    assert!(get_mediabox(&doc, pages[&3]).is_err());
+}
+
+#[test]
+fn test_document_api_matches_byte_api() {
+    let data = fs::read("example_docs/studocu-example.pdf").expect("Failed to read PDF");
+    let options = CleanOptions::default();
+
+    let mut doc = Document::load_mem(&data).unwrap();
+    let analysis = analyze_document(&doc);
+    let report = clean_document(&mut doc, &options).unwrap();
+    let (_, reader_report) = clean_reader(data.as_slice(), &options).unwrap();
+
+    assert_eq!(analysis.method_code, report.method_code);
+    let planned: Vec<u32> = analysis
+        .pages
+        .iter()
+        .filter(|x| x.delete)
+        .map(|x| x.page)
+        .collect();
+    assert_eq!(planned, report.deleted_pages);
+    assert_eq!(doc.get_pages().len() as u32, report.pages_after);
+    assert_eq!(report.status(), VerifyStatus::Passed);
+    assert_eq!(reader_report.deleted_pages, report.deleted_pages);
+}
+
+#[test]
+fn test_invalid_input_is_an_error() {
+    let result = clean_pdf_with_report(b"definitely not a pdf", &CleanOptions::default());
+    let error = result.unwrap_err();
+    assert!(matches!(error, Error::Parse(_)));
+    assert_eq!(error.code(), "PARSE_ERROR");
+
+    // Encrypted with a user password we don't have
+    let mut doc = Document::load("example_docs/studocu-example.pdf").unwrap();
+    let encrypt = doc.add_object(dictionary! {
+        "Filter" => "Standard",
+        "V" => 1,
+        "R" => 2,
+        "O" => Object::string_literal(vec![1u8; 32]),
+        "U" => Object::string_literal(vec![2u8; 32]),
+        "P" => -4,
+    });
+    doc.trailer.set("Encrypt", encrypt);
+    doc.trailer
+        .set("ID", vec![Object::string_literal(vec![3u8; 16]); 2]);
+    let mut data = Vec::new();
+    doc.save_to(&mut data).unwrap();
+    let error = load_document(&data).unwrap_err();
+    assert_eq!(error.code(), "ENCRYPTED_DOCUMENT");
+
+    // Encrypted with AES, which lopdf can't decrypt
+    let aes = dictionary! {
+        "Filter" => "Standard",
+        "V" => 4,
+        "R" => 4,
+        "CF" => dictionary! { "StdCF" => dictionary! { "CFM" => "AESV2", "Length" => 16 } },
+        "StmF" => "StdCF",
+        "StrF" => "StdCF",
+    };
+    doc.objects.insert(encrypt, Object::Dictionary(aes));
+    let mut data = Vec::new();
+    doc.save_to(&mut data).unwrap();
+    let error = load_document(&data).unwrap_err();
+    assert!(matches!(error, Error::Unsupported(_)));
+    assert!(
+        error.to_string().contains("AES-128 (V 4, R 4)"),
+        "{}",
+        error
+    );
+
+    // No pages to clean
+    let mut doc = Document::with_version("1.5");
+    let pages = doc.add_object(dictionary! { "Type" => "Pages", "Kids" => vec![], "Count" => 0 });
+    let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages });
+    doc.trailer.set("Root", catalog);
+    let mut data = Vec::new();
+    doc.save_to(&mut data).unwrap();
+    let error = load_document(&data).unwrap_err();
+    assert!(matches!(error, Error::Unsupported(_)));
+}
+
+#[test]
+fn test_plan_round_trips_and_reapplies() {
+    let data = fs::read("example_docs/studocu-example.pdf").expect("Failed to read PDF");
+    let options = CleanOptions::default();
+
+    let mut doc = Document::load_mem(&data).unwrap();
+    let plan = plan_document(&doc, &options);
+    assert_eq!(plan.method_code, 1);
+    assert_eq!(plan.delete, vec![1]);
+    assert_eq!(plan.pages.len(), doc.get_pages().len() - 1);
+
+    let json = serde_json::to_string(&plan).unwrap();
+    let reloaded: CleanPlan = serde_json::from_str(&json).unwrap();
+    assert_eq!(reloaded, plan);
+
+    // The same plan applied to an identical download gives the same document
+    let mut identical = Document::load_mem(&data).unwrap();
+    let report = apply_plan(&mut doc, &plan, &options).unwrap();
+    let identical_report = apply_plan(&mut identical, &reloaded, &options).unwrap();
+    assert_eq!(report.status(), VerifyStatus::Passed);
+    assert!(identical_report.warnings.is_empty());
+    assert_eq!(doc.objects, identical.objects);
+}
+
+#[test]
+fn test_plan_skips_pages_that_do_not_match() {
+    let data = fs::read("example_docs/studocu-example.pdf").expect("Failed to read PDF");
+    let mut doc = Document::load_mem(&data).unwrap();
+    let mut plan = plan_document(&doc, &CleanOptions::default());
+    plan.pages[0].id = (9999, 0);
+    plan.delete = vec![3, 1, 3, 999];
+
+    let report = apply_plan(&mut doc, &plan, &CleanOptions::default()).unwrap();
+    assert_eq!(report.deleted_pages, vec![1, 3]);
+    assert_eq!(report.pages_after, report.pages_before - 2);
+    assert!(matches!(
+        report.warnings.as_slice(),
+        [
+            Warning::PageSkipped { page: 2, .. },
+            Warning::PageOutOfRange { page: 999, .. }
+        ]
+    ));
+}
+
+#[test]
+fn test_delete_pages_rebuilds_the_page_tree() {
+    // Root -> [Node(1, 2), Node(3), 4], with an annotation of page 4 pointing at page 3
+    let mut doc = Document::with_version("1.5");
+    let root = doc.new_object_id();
+    let nodes = [doc.new_object_id(), doc.new_object_id()];
+    let page = |doc: &mut Document, parent| {
+        doc.add_object(dictionary! { "Type" => "Page", "Parent" => parent })
+    };
+    let pages: Vec<_> = [nodes[0], nodes[0], nodes[1], root]
+        .into_iter()
+        .map(|parent| page(&mut doc, parent))
+        .collect();
+    let link = doc.add_object(dictionary! { "Type" => "Annot", "P" => pages[2] });
+    doc.get_dictionary_mut(pages[3])
+        .unwrap()
+        .set("Annots", vec![link.into()]);
+    let node = |parent, kids: &[_]| {
+        dictionary! {
+            "Type" => "Pages",
+            "Parent" => parent,
+            "Kids" => kids.iter().map(|x| Object::Reference(*x)).collect::<Vec<_>>(),
+            "Count" => kids.len() as i64,
+        }
+    };
+    doc.objects.insert(nodes[0], node(root, &pages[..2]).into());
+    doc.objects
+        .insert(nodes[1], node(root, &pages[2..3]).into());
+    let mut root_node = node(root, &[nodes[0], nodes[1], pages[3]]);
+    root_node.remove(b"Parent");
+    root_node.set("Count", 4);
+    doc.objects.insert(root, root_node.into());
+    let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => root });
+    doc.trailer.set("Root", catalog);
+
+    let deletion = delete_pages(&mut doc, &[3, 1, 3, 9]);
+    assert_eq!(deletion.deleted, vec![1, 3]);
+    assert_eq!(deletion.out_of_range, vec![9]);
+    assert_eq!(
+        doc.get_pages().into_values().collect::<Vec<_>>(),
+        vec![pages[1], pages[3]]
+    );
+
+    let count = |id| {
+        doc.get_dictionary(id)
+            .unwrap()
+            .get(b"Count")
+            .unwrap()
+            .as_i64()
+            .unwrap()
+    };
+    assert_eq!(count(root), 2);
+    assert_eq!(count(nodes[0]), 1);
+    let root_kids = doc.get_dictionary(root).unwrap().get(b"Kids").unwrap();
+    assert_eq!(
+        root_kids.as_array().unwrap(),
+        &vec![Object::Reference(nodes[0]), Object::Reference(pages[3])]
+    );
+    assert!(!doc.objects.contains_key(&nodes[1]));
+    assert!(!doc.objects.contains_key(&pages[0]));
+    assert!(!doc.get_dictionary(link).unwrap().has(b"P"));
+}
+
+#[test]
+fn test_identity_fonts_decode_through_to_unicode() {
+    let doc = Document::load("example_docs/studocu-example.pdf").unwrap();
+    let pages = doc.get_pages();
+    let text = extract_page_text(&doc, pages[&2]).join(" ");
+    assert!(text.starts_with("ECOLOGÍA TEMA 15: DINÁMICA DE POBLACIONES"));
+    assert!(!text.contains('\u{FFFD}'));
+}
+
+#[test]
+fn test_huge_to_unicode_ranges_are_not_expanded() {
+    let mut doc = Document::with_version("1.5");
+    let cmap = b"1 begincodespacerange <00000000> <FFFFFFFF> endcodespacerange\n\
+        2 beginbfrange <00000000> <0FFFFFFF> <0041>\n\
+        <FFFFFFFE> <FFFFFFFF> [<0058> <0059> <005A>] endbfrange";
+    let cmap = doc.add_object(Stream::new(Dictionary::new(), cmap.to_vec()));
+    let font = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type0",
+        "BaseFont" => "Bomb",
+        "Encoding" => "Identity-H",
+        "ToUnicode" => cmap,
+    });
+    let content = b"BT /F1 12 Tf <0000000000000001FFFFFFFF> Tj ET".to_vec();
+    let content = doc.add_object(Stream::new(Dictionary::new(), content));
+    let pages = doc.new_object_id();
+    let page = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        "Contents" => content,
+        "Resources" => dictionary! { "Font" => dictionary! { "F1" => font } },
+    });
+    doc.objects.insert(
+        pages,
+        Object::Dictionary(
+            dictionary! { "Type" => "Pages", "Kids" => vec![page.into()], "Count" => 1 },
+        ),
+    );
+    let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages });
+    doc.trailer.set("Root", catalog);
+
+    let start = Instant::now();
+    assert_eq!(extract_page_text(&doc, page), vec!["ABY"]);
+    let data = fixtures::to_bytes(&mut doc);
+    clean_pdf_with_report(&data, &CleanOptions::default()).unwrap();
+    assert!(start.elapsed().as_secs() < 5);
+}
+
+#[test]
+fn test_extract_metadata() {
+    let doc = Document::load("example_docs/studocu-example.pdf").unwrap();
+    let metadata = extract_metadata(&doc).unwrap();
+    assert_eq!(metadata.file_name, "TEMA 15. DINÁMICA DE POBLACIONES");
+    assert_eq!(metadata.author, "Ecologia (Universidad Autónoma de Madrid)");
+
+    let empty = Document::with_version("1.5");
+    let error = extract_metadata(&empty).unwrap_err();
+    assert!(matches!(error, Error::MissingMetadata { found: 0 }));
+    assert_eq!(error.code(), "METADATA_NOT_FOUND");
+}
+
+#[test]
+fn test_progress_and_cancellation() {
+    let data = fs::read("example_docs/studocu-example.pdf").expect("Failed to read PDF");
+    let pages = Document::load_mem(&data).unwrap().get_pages().len() as u32;
+
+    let updates = RefCell::new(Vec::new());
+    let progress =
+        |stage: Stage, page: u32, total: u32| updates.borrow_mut().push((stage, page, total));
+    let hooks = Hooks {
+        progress: Some(&progress),
+        cancel: None,
+    };
+    clean_pdf_with_hooks(&data, &CleanOptions::default(), &hooks).unwrap();
+
+    let updates = updates.into_inner();
+    let count = |stage| updates.iter().filter(|x| x.0 == stage).count() as u32;
+    assert_eq!(updates[0], (Stage::Classifying, 2, pages));
+    assert_eq!(count(Stage::Classifying), pages - 1);
+    assert_eq!(count(Stage::Applying), pages - 1);
+    assert_eq!(count(Stage::Verifying), pages - 1);
+
+    // Cancelling from the callback stops the run before the next page
+    let token = CancellationToken::new();
+    let seen = RefCell::new(0);
+    let progress = |_: Stage, _: u32, _: u32| {
+        *seen.borrow_mut() += 1;
+        token.cancel();
+    };
+    let hooks = Hooks {
+        progress: Some(&progress),
+        cancel: Some(&token),
+    };
+    let error = clean_pdf_with_hooks(&data, &CleanOptions::default(), &hooks).unwrap_err();
+    assert!(matches!(error, Error::Cancelled));
+    assert_eq!(error.code(), "CANCELLED");
+    assert_eq!(*seen.borrow(), 1);
+
+    let doc = Document::load_mem(&data).unwrap();
+    let error = analyze_document_with_hooks(&doc, &hooks).unwrap_err();
+    assert!(matches!(error, Error::Cancelled));
+}
+
+#[test]
+fn test_cancelling_stops_later_pages() {
+    // Cancelling from the first Naive page stops the run without reporting any other page
+    let mut doc = fixtures::naive(&vec![PageType::BannerAds; parallel::chunk_size() * 3]);
+    let data = fixtures::to_bytes(&mut doc);
+    let options = CleanOptions {
+        force_naive: true,
+        ..Default::default()
+    };
+    let token = CancellationToken::new();
+    let seen = RefCell::new(Vec::new());
+    let progress = |stage: Stage, page: u32, _: u32| {
+        seen.borrow_mut().push((stage, page));
+        token.cancel();
+    };
+    let hooks = Hooks {
+        progress: Some(&progress),
+        cancel: Some(&token),
+    };
+    let error = clean_pdf_with_hooks(&data, &options, &hooks).unwrap_err();
+    assert!(matches!(error, Error::Cancelled));
+    assert_eq!(seen.into_inner(), vec![(Stage::Classifying, 1)]);
+
+    // Only the chunk that was running when the run was cancelled is processed
+    let items: Vec<usize> = (0..parallel::chunk_size() * 3).collect();
+    let token = CancellationToken::new();
+    let processed = AtomicUsize::new(0);
+    let result = parallel::for_each_chunked(
+        &items,
+        &mut (),
+        Some(&token),
+        |_, _| processed.fetch_add(1, Ordering::Relaxed),
+        |_, _, _| {
+            token.cancel();
+            Err(Error::Cancelled)
+        },
+    );
+    assert!(matches!(result, Err(Error::Cancelled)));
+    assert_eq!(processed.into_inner(), parallel::chunk_size());
+}
+
+/// Zlib-compresses `data`, as a `FlateDecode` stream stores it.
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn test_limits() {
+    let data = fs::read("example_docs/studocu-example.pdf").unwrap();
+    load_document_with_limits(&data, &Limits::default()).unwrap();
+
+    let limits = Limits {
+        max_pages: 1,
+        ..Default::default()
+    };
+    let error = load_document_with_limits(&data, &limits).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::LimitExceeded {
+            limit: Limit::Pages,
+            max: 1
+        }
+    ));
+    assert_eq!(error.code(), "LIMIT_EXCEEDED");
+
+    // A page content stream that inflates to 16 MB
+    let mut doc = Document::load_mem(&data).unwrap();
+    let page = doc.page_iter().next().unwrap();
+    let bomb = Stream::new(
+        dictionary! { "Filter" => "FlateDecode" },
+        deflate(&vec![b' '; 16 * 1024 * 1024]),
+    );
+    let bomb = doc.add_object(bomb);
+    doc.get_dictionary_mut(page).unwrap().set("Contents", bomb);
+    let mut bomb_data = Vec::new();
+    doc.save_to(&mut bomb_data).unwrap();
+
+    let limits = Limits {
+        max_stream_bytes: 1024 * 1024,
+        ..Default::default()
+    };
+    let options = CleanOptions {
+        limits,
+        ..Default::default()
+    };
+    let error = clean_pdf_with_report(&bomb_data, &options).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::LimitExceeded {
+            limit: Limit::StreamBytes,
+            ..
+        }
+    ));
+    let mut doc = Document::load_mem(&bomb_data).unwrap();
+    let error = clean_document(&mut doc, &options).unwrap_err();
+    assert!(matches!(error, Error::LimitExceeded { .. }));
+
+    let limits = Limits {
+        max_decompressed_bytes: 1024 * 1024,
+        ..Default::default()
+    };
+    let error = load_document_with_limits(&bomb_data, &limits).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::LimitExceeded {
+            limit: Limit::DecompressedBytes,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_limits_measure_xref_streams_before_inflating() {
+    // A cross-reference stream whose entries are followed by 16 MB of padding
+    let mut data = b"%PDF-1.5\n".to_vec();
+    let offset = data.len();
+    data.extend_from_slice(b"2 0 obj\n<</Type/Catalog/Pages 3 0 R>>\nendobj\n");
+    data.extend_from_slice(b"3 0 obj\n<</Type/Pages/Kids[]/Count 0>>\nendobj\n");
+    let pages = offset
+        + data[offset..]
+            .windows(7)
+            .position(|x| x == b"3 0 obj")
+            .unwrap();
+    let xref_offset = data.len();
+    let mut content = Vec::new();
+    for [kind, field] in [[0, 0], [1, xref_offset], [1, offset], [1, pages]] {
+        content.push(kind as u8);
+        content.extend_from_slice(&(field as u32).to_be_bytes());
+        content.push(0);
+    }
+    content.resize(16 * 1024 * 1024, 0);
+    let content = deflate(&content);
+    data.extend(
+        format!(
+            "1 0 obj\n<</Type/XRef/Size 4/W[1 4 1]/Root 2 0 R/Filter/FlateDecode/Length {}>>stream\n",
+            content.len()
+        )
+        .bytes(),
+    );
+    data.extend(content);
+    data.extend(format!("\nendstream\nendobj\nstartxref\n{}\n%%EOF\n", xref_offset).bytes());
+
+    let (doc, _) = Limits::default().load(&data).unwrap();
+    assert_eq!(
+        doc.get_object((2, 0)).unwrap().type_name().unwrap(),
+        "Catalog"
+    );
+    let limits = Limits {
+        max_stream_bytes: 1024 * 1024,
+        ..Default::default()
+    };
+    let error = limits.load(&data).err().unwrap();
+    assert!(matches!(
+        error,
+        Error::LimitExceeded {
+            limit: Limit::StreamBytes,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_limits_measure_object_streams_before_inflating() {
+    let mut doc = Document::load("example_docs/studocu-example.pdf").unwrap();
+    let objects = b"9999 0 42 ";
+    doc.add_object(Stream::new(
+        dictionary! { "Type" => "XObjStm", "N" => 1, "First" => 7, "Filter" => "FlateDecode" },
+        deflate(objects),
+    ));
+    let mut data = Vec::new();
+    doc.save_to(&mut data).unwrap();
+    // lopdf doesn't write object streams, so the type is patched in place
+    let at = data.windows(8).position(|x| x == b"/XObjStm").unwrap();
+    data[at..at + 8].copy_from_slice(b"/ObjStm ");
+
+    // Object streams are expanded like lopdf does
+    let expected = Document::load_mem(&data).unwrap();
+    let doc = load_document_with_limits(&data, &Limits::default()).unwrap();
+    assert_eq!(doc.objects.len(), expected.objects.len());
+    assert_eq!(doc.get_object((9999, 0)).unwrap(), &Object::Integer(42));
+
+    let limits = Limits {
+        max_stream_bytes: objects.len() - 1,
+        ..Limits::unlimited()
+    };
+    let error = load_document_with_limits(&data, &limits).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::LimitExceeded {
+            limit: Limit::StreamBytes,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_output_is_deterministic() {
+    let data = fs::read("example_docs/studocu-example.pdf").unwrap();
+    let options = CleanOptions::default();
+
+    let (first, _) = clean_pdf_with_report(&data, &options).unwrap();
+    let (second, _) = clean_pdf_with_report(&data, &options).unwrap();
+    let (from_reader, _) = clean_reader(data.as_slice(), &options).unwrap();
+    let digest = |x: &[u8]| format!("{:x}", md5::Md5::digest(x));
+    assert_eq!(digest(&first), digest(&second));
+    assert_eq!(digest(&first), digest(&from_reader));
+
+    // The /ID is derived from the content and objects are numbered from 1
+    let doc = Document::load_mem(&first).unwrap();
+    let id = doc.trailer.get(b"ID").unwrap().as_array().unwrap();
+    assert_eq!(id.len(), 2);
+    assert_eq!(id[0].as_str().unwrap().len(), 16);
+    assert_eq!(doc.objects.keys().next(), Some(&(1, 0)));
+    // The /ModDate is left as it was
+    let has_mod_date = |x: &[u8]| String::from_utf8_lossy(x).contains("/ModDate");
+    assert_eq!(has_mod_date(&first), has_mod_date(&data));
+
+    let options = CleanOptions {
+        mod_date: Some(1_700_000_000),
+        ..Default::default()
+    };
+    let (dated, _) = clean_pdf_with_report(&data, &options).unwrap();
+    let (dated_again, _) = clean_pdf_with_report(&data, &options).unwrap();
+    assert_eq!(dated, dated_again);
+    let doc = Document::load_mem(&dated).unwrap();
+    let info = doc
+        .trailer
+        .get(b"Info")
+        .and_then(Object::as_reference)
+        .unwrap();
+    let mod_date = doc.get_dictionary(info).unwrap().get(b"ModDate").unwrap();
+    assert!(mod_date.as_str().unwrap().starts_with(b"D:20231114221320"));
+
+    // A date that can't be stored is rejected before the document is cleaned
+    let options = CleanOptions {
+        mod_date: Some(i64::MAX),
+        ..Default::default()
+    };
+    let error = clean_pdf_with_report(&data, &options).unwrap_err();
+    assert!(matches!(error, Error::InvalidOptions(_)));
+    assert_eq!(error.code(), "INVALID_OPTIONS");
+    let error = clean_lazy(&data, &mut Vec::new(), &options).unwrap_err();
+    assert!(matches!(error, Error::InvalidOptions(_)));
+}
+
+#[test]
+fn test_skeleton_reproduces_detection() {
+    let example = Document::load("example_docs/studocu-example.pdf").unwrap();
+    let docs = [
+        fixtures::wuolah(3),
+        fixtures::wuolah_010624(2),
+        fixtures::studocu(2),
+        fixtures::naive(&[
+            PageType::FullPageAds,
+            PageType::BannerAds,
+            PageType::Watermark,
+        ]),
+        example,
+    ];
+    for doc in docs {
+        let skeleton = export_skeleton(&doc);
+        assert_eq!(analyze_document(&skeleton), analyze_document(&doc));
+        let options = CleanOptions::default();
+        assert_eq!(
+            plan_document(&skeleton, &options),
+            plan_document(&doc, &options)
+        );
+
+        let data = export_skeleton_pdf(&fixtures::to_bytes(&mut doc.clone())).unwrap();
+        let reloaded = Document::load_mem(&data).unwrap();
+        assert_eq!(analyze_document(&reloaded), analyze_document(&doc));
+    }
+}
+
+#[test]
+fn test_skeleton_removes_text_and_pixels() {
+    let doc = Document::load("example_docs/studocu-example.pdf").unwrap();
+    let skeleton = export_skeleton(&doc);
+    let pages = skeleton.get_pages();
+    let text = extract_page_text(&skeleton, pages[&2]).join(" ");
+    assert!(!text.contains("POBLACIONES"));
+    if let Ok(metadata) = extract_metadata(&skeleton) {
+        assert!(!metadata.author.contains("Madrid"));
+    }
+
+    for (id, object) in &skeleton.objects {
+        let Ok(stream) = object.as_stream() else {
+            continue;
+        };
+        assert!(!stream.dict.has(b"Filter"));
+        if stream.dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Image") {
+            assert!(stream.content.is_empty());
+            let original = doc.get_object(*id).unwrap().as_stream().unwrap();
+            assert_eq!(
+                stream.dict.get(b"Width").ok(),
+                original.dict.get(b"Width").ok()
+            );
+        }
+    }
+    // The operators are kept, only their string operands are blanked
+    let content = skeleton.get_page_content(pages[&2]).unwrap();
+    let content = String::from_utf8_lossy(&content);
+    assert!(content.contains("Tj") || content.contains("TJ"));
+    assert!(content.contains("xxx"));
+}
+
+#[test]
+fn test_page_index_matches_direct_lookups() {
+    let example = Document::load("example_docs/studocu-example.pdf").unwrap();
+    let docs = [
+        fixtures::wuolah(2),
+        fixtures::naive(&[PageType::BannerAds, PageType::Watermark, PageType::Idk]),
+        example,
+    ];
+    for doc in docs {
+        let index = PageIndex::new(&doc);
+        assert_eq!(index.len(), doc.get_pages().len());
+        for (number, id) in doc.get_pages() {
+            let page = index.get(number).unwrap();
+            assert_eq!(page.id, id);
+            assert_eq!(page.contents, doc.get_page_contents(id));
+            assert_eq!(
+                page.page_type().ok(),
+                PageType::get_page_type(&doc, &id).ok()
+            );
+            assert_eq!(page.logos(), get_logos(&doc, &id).unwrap_or_default());
+            assert_eq!(page.mediabox().ok(), get_mediabox(&doc, id).ok());
+        }
+        assert!(index.get(0).is_none());
+        assert!(index.get(index.len() as u32 + 1).is_none());
+    }
+}
+
+/// Saves a document with its dictionaries in an object stream and a cross-reference
+/// stream, which lopdf can't write.
+fn save_with_object_streams(doc: &Document) -> Vec<u8> {
+    let mut data = b"%PDF-1.5\n".to_vec();
+    let mut entries = std::collections::BTreeMap::new();
+    let container = doc.max_id + 1;
+    let (mut index, mut objects) = (String::new(), Vec::new());
+    for (id, object) in &doc.objects {
+        if let Object::Stream(_) = object {
+            entries.insert(id.0, (1, data.len() as u32, 0));
+            data.extend(format!("{} 0 obj\n", id.0).bytes());
+            write_object(&mut data, object);
+            data.extend_from_slice(b"\nendobj\n");
+        } else {
+            entries.insert(id.0, (2, container, entries.len() as u16));
+            index.push_str(&format!("{} {} ", id.0, objects.len()));
+            write_object(&mut objects, object);
+            objects.push(b' ');
+        }
+    }
+    // Number the compressed objects by their position in the object stream
+    let mut position = 0;
+    for entry in entries.values_mut().filter(|x| x.0 == 2) {
+        entry.2 = position;
+        position += 1;
+    }
+    let first = index.len() as i64;
+    let mut content = index.into_bytes();
+    content.extend(objects);
+    let object_stream = Stream::new(
+        dictionary! { "Type" => "ObjStm", "N" => position as i64, "First" => first, "Filter" => "FlateDecode" },
+        deflate(&content),
+    );
+    entries.insert(container, (1, data.len() as u32, 0));
+    data.extend(format!("{} 0 obj\n", container).bytes());
+    write_object(&mut data, &Object::Stream(object_stream));
+    data.extend_from_slice(b"\nendobj\n");
+
+    let xref_id = container + 1;
+    let xref_start = data.len();
+    entries.insert(xref_id, (1, xref_start as u32, 0));
+    let mut xref = Vec::new();
+    for id in 0..=xref_id {
+        let (kind, field, index) = entries.get(&id).copied().unwrap_or((0, 0, 0));
+        xref.push(kind);
+        xref.extend_from_slice(&field.to_be_bytes());
+        xref.extend_from_slice(&index.to_be_bytes());
+    }
+    let mut dict = dictionary! {
+        "Type" => "XRef",
+        "Size" => xref_id as i64 + 1,
+        "W" => vec![1.into(), 4.into(), 2.into()],
+    };
+    for key in [b"Root".as_slice(), b"Info"] {
+        if let Ok(value) = doc.trailer.get(key) {
+            dict.set(key, value.clone());
+        }
+    }
+    data.extend(format!("{} 0 obj\n", xref_id).bytes());
+    write_object(&mut data, &Object::Stream(Stream::new(dict, xref)));
+    data.extend(format!("\nendobj\nstartxref\n{}\n%%EOF\n", xref_start).bytes());
+    data
+}
+
+#[test]
+fn test_lazy_clean_matches_full_clean() {
+    let example = fs::read("example_docs/studocu-example.pdf").unwrap();
+    let mut inputs = vec![example];
+    for mut doc in [
+        fixtures::wuolah(3),
+        fixtures::wuolah_010624(2),
+        fixtures::studocu(2),
+        fixtures::naive(&[PageType::FullPageAds, PageType::BannerAds, PageType::Idk]),
+    ] {
+        inputs.push(fixtures::to_bytes(&mut doc));
+    }
+    let options = CleanOptions::default();
+
+    for data in inputs {
+        let (full, full_report) = clean_pdf_with_report(&data, &options).unwrap();
+        let mut lazy = Vec::new();
+        let report = clean_lazy(&data, &mut lazy, &options).unwrap();
+        assert_eq!(report.method_code, full_report.method_code);
+        assert_eq!(report.deleted_pages, full_report.deleted_pages);
+        assert_eq!(report.pages_after, full_report.pages_after);
+        assert_eq!(report.status(), VerifyStatus::Passed, "{:?}", report);
+
+        // The output is an incremental update, and reads like the fully cleaned document
+        assert!(lazy.starts_with(&data));
+        assert_eq!(
+            verify_pdf(&lazy, report.pages_after).status,
+            VerifyStatus::Passed
+        );
+        let (full, lazy) = (load_document(&full).unwrap(), load_document(&lazy).unwrap());
+        for (full_page, lazy_page) in full.page_iter().zip(lazy.page_iter()) {
+            assert_eq!(
+                extract_page_text(&full, full_page),
+                extract_page_text(&lazy, lazy_page)
+            );
+            assert_eq!(
+                get_mediabox(&full, full_page).unwrap(),
+                get_mediabox(&lazy, lazy_page).unwrap()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_lazy_clean_reads_object_streams() {
+    let mut doc = fixtures::studocu(2);
+    let data = save_with_object_streams(&doc);
+    let lazy = LazyDocument::load(&data).unwrap();
+    let root = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+    assert_eq!(
+        &lazy.get_object(root).unwrap(),
+        doc.get_object(root).unwrap()
+    );
+
+    let full = clean_document(&mut doc, &CleanOptions::default()).unwrap();
+    let mut output = Vec::new();
+    let report = clean_lazy(&data, &mut output, &CleanOptions::default()).unwrap();
+    assert_eq!(report.deleted_pages, full.deleted_pages);
+    // The update is written as a cross-reference stream, like the original
+    let cleaned = load_document(&output).unwrap();
+    assert_eq!(cleaned.get_pages().len() as u32, full.pages_after);
+    assert!(String::from_utf8_lossy(&output[data.len()..]).contains("/Type/XRef"));
+
+    let limits = Limits {
+        max_pages: 1,
+        ..Default::default()
+    };
+    let options = CleanOptions {
+        limits,
+        ..Default::default()
+    };
+    let error = clean_lazy(&data, &mut Vec::new(), &options).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::LimitExceeded {
+            limit: Limit::Pages,
+            ..
+        }
+    ));
+}
+
+/// Replaces the value of the last `/Size` in `data`, the one of its last trailer.
+///
+/// An `/Index` with the old value is added, so that a cross-reference stream without one
+/// still lists the same entries.
+fn with_size(data: &[u8], size: &str) -> Vec<u8> {
+    let at = data.windows(5).rposition(|x| x == b"/Size").unwrap() + 5;
+    let start = at + data[at..].iter().take_while(|x| **x == b' ').count();
+    let end = start
+        + data[start..]
+            .iter()
+            .take_while(|x| x.is_ascii_digit())
+            .count();
+    let old = String::from_utf8_lossy(&data[start..end]);
+    let value = format!(" {}/Index[0 {}]", size, old);
+    [&data[..at], value.as_bytes(), &data[end..]].concat()
+}
+
+/// A document whose cross-reference stream, object 1, lists object 2 with `entry`, given
+/// the offset of `object`, which is written as object 2.
+fn with_xref_entry(object: &str, entry: impl Fn(usize) -> [u32; 3]) -> Vec<u8> {
+    let mut data = b"%PDF-1.5\n".to_vec();
+    let offset = data.len();
+    data.extend(format!("2 0 obj\n{}\nendobj\n", object).bytes());
+    let xref_offset = data.len();
+    let mut content = Vec::new();
+    for [kind, field, index] in [[0, 0, 0], [1, xref_offset as u32, 0], entry(offset)] {
+        content.push(kind as u8);
+        content.extend_from_slice(&field.to_be_bytes());
+        content.push(index as u8);
+    }
+    data.extend(
+        format!(
+            "1 0 obj\n<</Type/XRef/Size 3/W[1 4 1]/Root 2 0 R/Length {}>>stream\n",
+            content.len()
+        )
+        .bytes(),
+    );
+    data.extend(content);
+    data.extend(format!("\nendstream\nendobj\nstartxref\n{}\n%%EOF\n", xref_offset).bytes());
+    data
+}
+
+#[test]
+fn test_lazy_clean_stops_at_reference_cycles() {
+    // Object 2 is stored in itself
+    let mut inputs = vec![with_xref_entry("<</Type/Catalog>>", |_| [2, 2, 0])];
+    // Object 2 is a stream whose /Length is itself
+    for length in ["2 0 R", "2 0R", "02 %(\n0 R"] {
+        let object = format!("<</Length {}>>stream\nabc\nendstream", length);
+        inputs.push(with_xref_entry(&object, |offset| [1, offset as u32, 0]));
+    }
+    for data in inputs {
+        let lazy = LazyDocument::load(&data).unwrap();
+        assert!(lazy.get_object((2, 0)).is_err());
+        assert!(clean_lazy(&data, &mut Vec::new(), &CleanOptions::default()).is_err());
+    }
+}
+
+/// A document with `objects` written as objects 2 and up, listed by a cross-reference
+/// table, with object 2 as its catalog.
+fn with_objects(objects: &[String]) -> Vec<u8> {
+    let mut data = b"%PDF-1.5\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(data.len());
+        data.extend(format!("{} 0 obj\n{}\nendobj\n", i + 2, object).bytes());
+    }
+    let xref_offset = data.len();
+    data.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 2).bytes());
+    data.extend_from_slice(b"0000000000 65535 f \n");
+    for offset in offsets {
+        data.extend(format!("{:010} 00000 n \n", offset).bytes());
+    }
+    data.extend(
+        format!(
+            "trailer\n<</Size {}/Root 2 0 R>>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 2,
+            xref_offset
+        )
+        .bytes(),
+    );
+    data
+}
+
+#[test]
+fn test_clean_stops_at_length_cycles() {
+    let stream = |length: &str| format!("<</Length {}>>stream\nabc\nendstream", length);
+    let catalog = "<</Type/Catalog/Pages 3 0 R>>".to_string();
+    let pages = "<</Type/Pages/Kids[]/Count 0>>".to_string();
+    let inputs = [
+        // A stream whose /Length is itself
+        vec![catalog.clone(), pages.clone(), stream("4 0 R")],
+        // Two streams whose /Length is the other
+        vec![
+            catalog.clone(),
+            pages.clone(),
+            stream("5 0R"),
+            stream("4 0 R"),
+        ],
+        // A long chain of streams whose /Length is the next one
+        [catalog.clone(), pages.clone()]
+            .into_iter()
+            .chain((4..10_004).map(|id| stream(&format!("{} 0 R", id + 1))))
+            .chain(["3".to_string()])
+            .collect(),
+    ];
+    for objects in inputs {
+        let data = with_objects(&objects);
+        let error = load_document(&data).unwrap_err();
+        assert!(
+            matches!(error, Error::Parse(lopdf::Error::ReferenceLimit)),
+            "{:?}",
+            error
+        );
+        assert!(clean_pdf_with_report(&data, &CleanOptions::default()).is_err());
+    }
+
+    // A /Length stored in another object, or that only looks like a reference, is read
+    for length in ["5 0 R", "(4 0 R) 3", "[4 0 R] 3"] {
+        let data = with_objects(&[catalog.clone(), pages.clone(), stream(length), "3".into()]);
+        let error = load_document(&data).unwrap_err();
+        assert!(matches!(error, Error::Unsupported(_)), "{:?}", error);
+    }
+}
+
+#[test]
+fn test_lazy_clean_charges_inflated_streams() {
+    let limits = Limits {
+        max_stream_bytes: 1024 * 1024,
+        ..Default::default()
+    };
+    let options = CleanOptions {
+        limits,
+        ..Default::default()
+    };
+    let is_stream_limit = |error| {
+        matches!(
+            error,
+            Error::LimitExceeded {
+                limit: Limit::StreamBytes,
+                ..
+            }
+        )
+    };
+
+    // An object stream that inflates to 16 MB
+    let mut doc = fixtures::studocu(2);
+    doc.add_object(Object::string_literal(vec![b' '; 16 * 1024 * 1024]));
+    let data = save_with_object_streams(&doc);
+    let lazy = LazyDocument::load_with_limits(&data, &limits).unwrap();
+    let root = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+    assert!(is_stream_limit(lazy.get_object(root).unwrap_err()));
+    assert!(is_stream_limit(
+        clean_lazy(&data, &mut Vec::new(), &options).unwrap_err()
+    ));
+
+    // A cross-reference stream larger than the limit
+    let data = save_with_object_streams(&fixtures::studocu(2));
+    let limits = Limits {
+        max_stream_bytes: 16,
+        ..Default::default()
+    };
+    let error = LazyDocument::load_with_limits(&data, &limits)
+        .err()
+        .unwrap();
+    assert!(is_stream_limit(error));
+
+    // The content stream of a banner page, which is wrapped, that inflates to 16 MB
+    let mut doc = fixtures::naive(&[PageType::BannerAds, PageType::Idk]);
+    let page = doc.page_iter().next().unwrap();
+    let bomb = doc.add_object(Stream::new(
+        dictionary! { "Filter" => "FlateDecode" },
+        deflate(&vec![b' '; 16 * 1024 * 1024]),
+    ));
+    doc.get_dictionary_mut(page).unwrap().set("Contents", bomb);
+    let data = fixtures::to_bytes(&mut doc);
+    assert!(is_stream_limit(
+        clean_lazy(&data, &mut Vec::new(), &options).unwrap_err()
+    ));
+}
+
+#[test]
+fn test_lazy_clean_clamps_trailer_size() {
+    // An out of range /Size in the trailer or the cross-reference stream doesn't overflow
+    // the numbers of the new objects
+    let options = CleanOptions {
+        mod_date: Some(1_700_000_000),
+        ..Default::default()
+    };
+    let doc = fixtures::studocu(2);
+    for data in [
+        fixtures::to_bytes(&mut doc.clone()),
+        save_with_object_streams(&doc),
+    ] {
+        for size in ["4294967296", "4294967295"] {
+            let data = with_size(&data, size);
+            let mut output = Vec::new();
+            let report = clean_lazy(&data, &mut output, &options).unwrap();
+            assert_eq!(report.status(), VerifyStatus::Passed, "{:?}", report);
+            let cleaned = load_document(&output).unwrap();
+            assert_eq!(cleaned.get_pages().len() as u32, report.pages_after);
+            let size = cleaned.trailer.get(b"Size").unwrap().as_i64().unwrap();
+            assert!(size < u32::MAX as i64, "/Size {}", size);
+        }
+    }
+}
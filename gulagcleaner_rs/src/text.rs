@@ -0,0 +1,390 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use lopdf::{content::Content, Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+
+/// Fraction of non-ad words that may disappear before a warning is raised.
+pub const TEXT_LOSS_THRESHOLD: f32 = 0.05;
+
+/// Lowercase fragments of the slogans and stamps the platforms add to every document.
+/// Any line containing one of them is ad text and is allowed to disappear.
+const AD_MARKERS: [&str; 6] = [
+    "wuolah",
+    "studocu",
+    "descargado por",
+    "lomoarcpsd",
+    "reservados todos los derechos",
+    "no se permite la explotación económica",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+/// Word counts of the non-ad text of the kept pages, before and after cleaning.
+pub struct TextComparison {
+    pub words_before: usize,
+    pub words_after: usize,
+    /// Words present in the original kept pages but missing from the cleaned ones.
+    pub words_lost: usize,
+}
+
+impl TextComparison {
+    /// Fraction of the original non-ad words that were lost.
+    pub fn lost_ratio(&self) -> f32 {
+        if self.words_before == 0 {
+            0.0
+        } else {
+            self.words_lost as f32 / self.words_before as f32
+        }
+    }
+}
+
+/// A font's `/ToUnicode` CMap, mapping character codes to text.
+#[derive(Debug, Default)]
+struct ToUnicode {
+    /// The byte lengths of the character codes, longest first.
+    code_lengths: Vec<usize>,
+    map: HashMap<(usize, u32), String>,
+    /// The `bfrange` entries that map a range of codes to consecutive text, resolved when a
+    /// code is decoded so a huge range costs no more than a small one.
+    ranges: Vec<CodeRange>,
+}
+
+/// A range of character codes mapped to consecutive text.
+#[derive(Debug)]
+struct CodeRange {
+    /// The byte length of the codes.
+    len: usize,
+    low: u32,
+    high: u32,
+    /// The UTF-16BE text of `low`, whose last byte is incremented for the next codes.
+    text: Vec<u8>,
+}
+
+impl CodeRange {
+    fn get(&self, len: usize, code: u32) -> Option<String> {
+        if len != self.len || !(self.low..=self.high).contains(&code) {
+            return None;
+        }
+        let mut text = self.text.clone();
+        if let Some(last) = text.last_mut() {
+            *last = last.wrapping_add((code - self.low) as u8);
+        }
+        Some(utf16_text(&text))
+    }
+}
+
+impl ToUnicode {
+    fn parse(data: &[u8]) -> ToUnicode {
+        let tokens = cmap_tokens(data);
+        let mut cmap = ToUnicode::default();
+        let mut i = 0;
+        while i < tokens.len() {
+            match tokens[i] {
+                CMapToken::Keyword(b"begincodespacerange") => {
+                    i += 1;
+                    while let Some(CMapToken::Hex(low)) = tokens.get(i) {
+                        if !cmap.code_lengths.contains(&low.len()) {
+                            cmap.code_lengths.push(low.len());
+                        }
+                        i += 2;
+                    }
+                }
+                CMapToken::Keyword(b"beginbfchar") => {
+                    i += 1;
+                    while let (Some(CMapToken::Hex(code)), Some(CMapToken::Hex(text))) =
+                        (tokens.get(i), tokens.get(i + 1))
+                    {
+                        cmap.insert(code, code_value(code), utf16_text(text));
+                        i += 2;
+                    }
+                }
+                CMapToken::Keyword(b"beginbfrange") => {
+                    i += 1;
+                    while let (Some(CMapToken::Hex(low)), Some(CMapToken::Hex(high))) =
+                        (tokens.get(i), tokens.get(i + 1))
+                    {
+                        let (low_value, high_value) = (code_value(low), code_value(high));
+                        i += 2;
+                        match tokens.get(i) {
+                            Some(CMapToken::Hex(text)) => {
+                                if low_value <= high_value {
+                                    cmap.ranges.push(CodeRange {
+                                        len: low.len(),
+                                        low: low_value,
+                                        high: high_value,
+                                        text: text.clone(),
+                                    });
+                                }
+                                i += 1;
+                            }
+                            Some(CMapToken::ArrayStart) => {
+                                i += 1;
+                                let mut code = Some(low_value).filter(|x| *x <= high_value);
+                                while let Some(CMapToken::Hex(text)) = tokens.get(i) {
+                                    // Entries past the end of the range are ignored.
+                                    if let Some(value) = code {
+                                        cmap.insert(low, value, utf16_text(text));
+                                        code = value.checked_add(1).filter(|x| *x <= high_value);
+                                    }
+                                    i += 1;
+                                }
+                                i += 1;
+                            }
+                            _ => break,
+                        }
+                    }
+                }
+                _ => i += 1,
+            }
+        }
+        if cmap.code_lengths.is_empty() {
+            cmap.code_lengths = cmap
+                .map
+                .keys()
+                .map(|x| x.0)
+                .chain(cmap.ranges.iter().map(|x| x.len))
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect();
+        }
+        cmap.code_lengths.sort_unstable_by(|a, b| b.cmp(a));
+        cmap
+    }
+
+    fn insert(&mut self, code: &[u8], value: u32, text: String) {
+        self.map.insert((code.len(), value), text);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.map.is_empty() && self.ranges.is_empty()
+    }
+
+    /// The text of a code. Single codes take precedence over ranges, and later ranges over
+    /// earlier ones.
+    fn get(&self, len: usize, code: u32) -> Option<String> {
+        if let Some(text) = self.map.get(&(len, code)) {
+            return Some(text.clone());
+        }
+        self.ranges.iter().rev().find_map(|x| x.get(len, code))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> String {
+        let shortest = self.code_lengths.last().copied().unwrap_or(1).max(1);
+        let mut text = String::new();
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let found = self.code_lengths.iter().find_map(|len| {
+                let code = rest.get(..*len)?;
+                Some((*len, self.get(*len, code_value(code))?))
+            });
+            match found {
+                Some((len, decoded)) => {
+                    text.push_str(&decoded);
+                    rest = &rest[len..];
+                }
+                None => {
+                    text.push('\u{FFFD}');
+                    rest = &rest[shortest.min(rest.len())..];
+                }
+            }
+        }
+        text
+    }
+}
+
+enum CMapToken<'a> {
+    Hex(Vec<u8>),
+    ArrayStart,
+    ArrayEnd,
+    Keyword(&'a [u8]),
+}
+
+fn cmap_tokens(data: &[u8]) -> Vec<CMapToken<'_>> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            b'<' if data.get(i + 1) == Some(&b'<') => i += 2,
+            b'<' => {
+                let end = data[i..]
+                    .iter()
+                    .position(|x| *x == b'>')
+                    .map_or(data.len(), |x| i + x);
+                let digits: Vec<u8> = data[i + 1..end]
+                    .iter()
+                    .filter(|x| x.is_ascii_hexdigit())
+                    .copied()
+                    .collect();
+                let bytes = digits
+                    .chunks(2)
+                    .map(|x| {
+                        let x = std::str::from_utf8(x).unwrap_or("0");
+                        u8::from_str_radix(&format!("{:0<2}", x), 16).unwrap_or(0)
+                    })
+                    .collect();
+                tokens.push(CMapToken::Hex(bytes));
+                i = end + 1;
+            }
+            b'[' => {
+                tokens.push(CMapToken::ArrayStart);
+                i += 1;
+            }
+            b']' => {
+                tokens.push(CMapToken::ArrayEnd);
+                i += 1;
+            }
+            x if x.is_ascii_alphabetic() => {
+                let end = data[i..]
+                    .iter()
+                    .position(|x| !x.is_ascii_alphanumeric())
+                    .map_or(data.len(), |x| i + x);
+                tokens.push(CMapToken::Keyword(&data[i..end]));
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+    tokens
+}
+
+fn code_value(code: &[u8]) -> u32 {
+    code.iter().fold(0, |acc, x| (acc << 8) | *x as u32)
+}
+
+fn utf16_text(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks(2)
+        .map(|x| u16::from_be_bytes([x[0], *x.get(1).unwrap_or(&0)]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// How the strings drawn with a font are turned into text.
+enum FontText<'a> {
+    ToUnicode(ToUnicode),
+    Encoding(&'a str),
+}
+
+impl FontText<'_> {
+    fn decode(&self, text: &mut String, bytes: &[u8]) {
+        match self {
+            FontText::ToUnicode(cmap) => text.push_str(&cmap.decode(bytes)),
+            FontText::Encoding(x) if x.starts_with("Identity") => {
+                text.push(' ');
+                text.extend(bytes.iter().map(|b| format!("{:02x}", b)));
+                text.push(' ');
+            }
+            FontText::Encoding(x) => text.push_str(&Document::decode_text(Some(x), bytes)),
+        }
+    }
+}
+
+fn font_text<'a>(doc: &'a Document, font: &'a lopdf::Dictionary) -> FontText<'a> {
+    let cmap = font
+        .get(b"ToUnicode")
+        .and_then(|x| doc.dereference(x))
+        .and_then(|(_, x)| x.as_stream())
+        .map(|x| {
+            ToUnicode::parse(
+                &x.decompressed_content()
+                    .unwrap_or_else(|_| x.content.clone()),
+            )
+        });
+    match cmap {
+        Ok(cmap) if !cmap.is_empty() => FontText::ToUnicode(cmap),
+        _ => FontText::Encoding(font.get_font_encoding()),
+    }
+}
+
+/// Extracts the text of a page, one entry per text object.
+///
+/// Fonts are decoded through their `/ToUnicode` map when they have one. Strings drawn with
+/// fonts that lopdf can't decode otherwise (such as `Identity-H`) are kept as hex glyph runs,
+/// so the same page can still be compared before and after cleaning.
+pub fn extract_page_text(doc: &Document, page: ObjectId) -> Vec<String> {
+    let fonts: BTreeMap<Vec<u8>, FontText> = doc
+        .get_page_fonts(page)
+        .into_iter()
+        .map(|(name, font)| (name, font_text(doc, font)))
+        .collect();
+    let Ok(content) = doc
+        .get_page_content(page)
+        .and_then(|data| Content::decode(&data))
+    else {
+        return Vec::new();
+    };
+
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut font = None;
+    for operation in &content.operations {
+        match operation.operator.as_str() {
+            "Tf" => {
+                font = operation
+                    .operands
+                    .first()
+                    .and_then(|x| x.as_name().ok())
+                    .and_then(|x| fonts.get(x));
+            }
+            "Tj" | "TJ" | "'" | "\"" => collect_text(&mut line, font, &operation.operands),
+            "ET" => lines.push(std::mem::take(&mut line)),
+            _ => {}
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn collect_text(text: &mut String, font: Option<&FontText>, operands: &[Object]) {
+    for operand in operands {
+        match operand {
+            Object::String(bytes, _) => match font {
+                Some(font) => font.decode(text, bytes),
+                None => text.push_str(&Document::decode_text(None, bytes)),
+            },
+            Object::Array(arr) => collect_text(text, font, arr),
+            Object::Integer(i) if *i < -100 => text.push(' '),
+            Object::Real(i) if *i < -100.0 => text.push(' '),
+            _ => {}
+        }
+    }
+}
+
+/// Returns `true` if a line of text is one of the platforms' ad slogans or stamps.
+pub fn is_ad_text(line: &str) -> bool {
+    let line = line.to_lowercase();
+    AD_MARKERS.iter().any(|marker| line.contains(marker))
+}
+
+fn count_words<'a>(lines: impl Iterator<Item = &'a String>) -> HashMap<String, usize> {
+    let mut words = HashMap::new();
+    for line in lines.filter(|x| !is_ad_text(x)) {
+        for word in line.split_whitespace() {
+            *words.entry(word.to_lowercase()).or_insert(0) += 1;
+        }
+    }
+    words
+}
+
+/// Compares the non-ad words of the original kept pages with the ones of the cleaned pages.
+///
+/// # Arguments
+///
+/// * `before` - The text lines of every kept page of the original document.
+/// * `after` - The text lines of every page of the cleaned document.
+pub fn compare_text(before: &[Vec<String>], after: &[Vec<String>]) -> TextComparison {
+    let before = count_words(before.iter().flatten());
+    let after = count_words(after.iter().flatten());
+
+    let words_lost = before
+        .iter()
+        .map(|(word, count)| count.saturating_sub(*after.get(word).unwrap_or(&0)))
+        .sum();
+
+    TextComparison {
+        words_before: before.values().sum(),
+        words_after: after.values().sum(),
+        words_lost,
+    }
+}
@@ -0,0 +1,337 @@
+use std::{collections::HashSet, fmt};
+
+use lopdf::{content::Content, Dictionary, Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+
+use crate::models::page_type::LOGO_DIMS;
+use crate::progress::{uncancellable, Hooks, Stage};
+
+/// Resource categories checked against the operators that reference them.
+const CHECKED_RESOURCES: [(&str, &[u8]); 4] = [
+    ("Do", b"XObject"),
+    ("Tf", b"Font"),
+    ("gs", b"ExtGState"),
+    ("sh", b"Shading"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+/// Overall outcome of the verification of a cleaned document.
+pub enum VerifyStatus {
+    /// Every check passed.
+    #[default]
+    Passed,
+    /// The document is usable, but some pages are suspicious.
+    Degraded,
+    /// The document is broken or does not match the cleaning plan.
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+/// A single problem found while verifying a cleaned document.
+pub enum VerifyIssue {
+    /// The output could not be parsed back into a document.
+    Unparseable { reason: String },
+    /// The output does not have the number of pages the cleaner planned for.
+    PageCountMismatch { expected: u32, found: u32 },
+    /// Every page was deleted, so the output has nothing left to read.
+    NoPagesLeft,
+    /// A kept page has no content to draw.
+    EmptyContents { page: u32 },
+    /// A kept page has content streams that are missing or cannot be decoded.
+    UndecodableContents { page: u32, reason: String },
+    /// A content stream references a resource that the page does not define.
+    MissingResource {
+        page: u32,
+        category: String,
+        name: String,
+    },
+    /// A known ad element is still present on a kept page.
+    AdSignature { page: u32, signature: String },
+}
+
+impl VerifyIssue {
+    /// Returns `true` if the issue makes the whole document unusable.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            VerifyIssue::Unparseable { .. }
+                | VerifyIssue::PageCountMismatch { .. }
+                | VerifyIssue::NoPagesLeft
+        )
+    }
+}
+
+impl fmt::Display for VerifyIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyIssue::Unparseable { reason } => write!(f, "output does not parse: {}", reason),
+            VerifyIssue::PageCountMismatch { expected, found } => {
+                write!(f, "expected {} pages, found {}", expected, found)
+            }
+            VerifyIssue::NoPagesLeft => write!(f, "every page was deleted"),
+            VerifyIssue::EmptyContents { page } => write!(f, "page {} has no contents", page),
+            VerifyIssue::UndecodableContents { page, reason } => {
+                write!(f, "page {} has undecodable contents: {}", page, reason)
+            }
+            VerifyIssue::MissingResource {
+                page,
+                category,
+                name,
+            } => write!(f, "page {} references missing {} /{}", page, category, name),
+            VerifyIssue::AdSignature { page, signature } => {
+                write!(f, "page {} still contains {}", page, signature)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// The result of verifying a cleaned document.
+pub struct Verification {
+    pub status: VerifyStatus,
+    pub issues: Vec<VerifyIssue>,
+}
+
+impl Verification {
+    pub(crate) fn from_issues(issues: Vec<VerifyIssue>) -> Verification {
+        let status = if issues.iter().any(VerifyIssue::is_fatal) {
+            VerifyStatus::Failed
+        } else if issues.is_empty() {
+            VerifyStatus::Passed
+        } else {
+            VerifyStatus::Degraded
+        };
+        Verification { status, issues }
+    }
+}
+
+/// Verifies the bytes of a cleaned PDF.
+///
+/// # Arguments
+///
+/// * `data` - The cleaned PDF document data.
+/// * `expected_pages` - The number of pages the cleaning plan should have left.
+///
+/// # Returns
+///
+/// A `Verification` whose status is `Failed` if the data does not parse back into a document.
+pub fn verify_pdf(data: &[u8], expected_pages: u32) -> Verification {
+    uncancellable(verify_pdf_with_hooks(
+        data,
+        expected_pages,
+        &Hooks::default(),
+    ))
+}
+
+pub(crate) fn verify_pdf_with_hooks(
+    data: &[u8],
+    expected_pages: u32,
+    hooks: &Hooks,
+) -> crate::Result<Verification> {
+    match Document::load_mem(data) {
+        Ok(doc) => verify_document_with_hooks(&doc, expected_pages, hooks),
+        Err(e) => Ok(Verification::from_issues(vec![VerifyIssue::Unparseable {
+            reason: e.to_string(),
+        }])),
+    }
+}
+
+/// Verifies an already parsed cleaned document.
+///
+/// It checks that the page count matches the plan and that some page is left, that every page has non-empty and
+/// decodable contents, that every resource referenced by the contents exists, and that
+/// no known ad signature remains.
+pub fn verify_document(doc: &Document, expected_pages: u32) -> Verification {
+    uncancellable(verify_document_with_hooks(
+        doc,
+        expected_pages,
+        &Hooks::default(),
+    ))
+}
+
+pub(crate) fn verify_document_with_hooks(
+    doc: &Document,
+    expected_pages: u32,
+    hooks: &Hooks,
+) -> crate::Result<Verification> {
+    let pages = doc.get_pages();
+    let mut issues = page_count_issues(expected_pages, pages.len() as u32);
+
+    for (&page_number, &page_id) in &pages {
+        hooks.page(Stage::Verifying, page_number, pages.len() as u32)?;
+        verify_page(doc, page_number, page_id, &mut issues);
+    }
+
+    Ok(Verification::from_issues(issues))
+}
+
+/// Checks the number of pages of a cleaned document against the plan.
+///
+/// A document left without pages fails even if the plan deleted every page.
+pub(crate) fn page_count_issues(expected: u32, found: u32) -> Vec<VerifyIssue> {
+    let mut issues = Vec::new();
+    if found != expected {
+        issues.push(VerifyIssue::PageCountMismatch { expected, found });
+    }
+    if found == 0 {
+        issues.push(VerifyIssue::NoPagesLeft);
+    }
+    issues
+}
+
+fn verify_page(doc: &Document, page_number: u32, page_id: ObjectId, issues: &mut Vec<VerifyIssue>) {
+    for id in doc.get_page_contents(page_id) {
+        if let Err(e) = doc.get_object(id).and_then(Object::as_stream) {
+            issues.push(VerifyIssue::UndecodableContents {
+                page: page_number,
+                reason: format!("content stream {} {} R: {}", id.0, id.1, e),
+            });
+            return;
+        }
+    }
+
+    let content = match doc
+        .get_page_content(page_id)
+        .and_then(|data| Content::decode(&data))
+    {
+        Ok(content) => content,
+        Err(e) => {
+            issues.push(VerifyIssue::UndecodableContents {
+                page: page_number,
+                reason: e.to_string(),
+            });
+            return;
+        }
+    };
+    if content.operations.is_empty() {
+        issues.push(VerifyIssue::EmptyContents { page: page_number });
+        return;
+    }
+
+    let resources = page_resources(doc, page_id);
+    let mut reported = HashSet::new();
+    for operation in &content.operations {
+        let Some((_, category)) = CHECKED_RESOURCES
+            .iter()
+            .find(|(operator, _)| *operator == operation.operator)
+        else {
+            continue;
+        };
+        let Some(name) = operation.operands.first().and_then(|x| x.as_name().ok()) else {
+            continue;
+        };
+
+        match resource_entry(doc, &resources, category, name) {
+            None => {
+                if reported.insert((category.to_vec(), name.to_vec())) {
+                    issues.push(VerifyIssue::MissingResource {
+                        page: page_number,
+                        category: String::from_utf8_lossy(category).into_owned(),
+                        name: String::from_utf8_lossy(name).into_owned(),
+                    });
+                }
+            }
+            Some(object) if *category == b"XObject" && is_visible_logo(doc, object) => {
+                issues.push(VerifyIssue::AdSignature {
+                    page: page_number,
+                    signature: format!("logo image /{}", String::from_utf8_lossy(name)),
+                });
+            }
+            Some(_) => {}
+        }
+    }
+
+    let ad_links = page_annotations(doc, page_id)
+        .iter()
+        .filter(|annot| is_uri_link(doc, annot))
+        .count();
+    if ad_links > 0 {
+        issues.push(VerifyIssue::AdSignature {
+            page: page_number,
+            signature: format!("{} link annotation(s)", ad_links),
+        });
+    }
+}
+
+/// Collects the resource dictionaries of a page, including the inherited ones.
+fn page_resources(doc: &Document, page_id: ObjectId) -> Vec<&Dictionary> {
+    let (direct, inherited) = doc.get_page_resources(page_id);
+    direct
+        .into_iter()
+        .chain(
+            inherited
+                .into_iter()
+                .filter_map(|id| doc.get_dictionary(id).ok()),
+        )
+        .collect()
+}
+
+/// The annotation dictionaries of a page.
+///
+/// Unlike `Document::get_page_annotations`, an `/Annots` entry that isn't an array is
+/// ignored instead of panicking.
+fn page_annotations(doc: &Document, page_id: ObjectId) -> Vec<&Dictionary> {
+    let annots = doc
+        .get_dictionary(page_id)
+        .and_then(|page| page.get(b"Annots"))
+        .and_then(|annots| doc.dereference(annots))
+        .and_then(|(_, annots)| annots.as_array());
+    match annots {
+        Ok(annots) => annots
+            .iter()
+            .filter_map(|annot| doc.dereference(annot).ok())
+            .filter_map(|(_, annot)| annot.as_dict().ok())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn resource_entry<'a>(
+    doc: &'a Document,
+    resources: &[&'a Dictionary],
+    category: &[u8],
+    name: &[u8],
+) -> Option<&'a Object> {
+    resources.iter().find_map(|resource| {
+        let (_, entries) = doc.dereference(resource.get(category).ok()?).ok()?;
+        entries.as_dict().ok()?.get(name).ok()
+    })
+}
+
+fn is_visible_logo(doc: &Document, xobject: &Object) -> bool {
+    let Ok((_, Object::Stream(stream))) = doc.dereference(xobject) else {
+        return false;
+    };
+    let dict = &stream.dict;
+    let is_image = dict
+        .get(b"Subtype")
+        .and_then(Object::as_name)
+        .map(|subtype| subtype.starts_with(b"Image"))
+        .unwrap_or(false);
+    match (
+        dict.get(b"Height").and_then(Object::as_i64),
+        dict.get(b"Width").and_then(Object::as_i64),
+    ) {
+        (Ok(height), Ok(width)) => is_image && LOGO_DIMS.contains(&(height, width)),
+        _ => false,
+    }
+}
+
+fn is_uri_link(doc: &Document, annot: &Dictionary) -> bool {
+    let is_link = annot
+        .get(b"Subtype")
+        .and_then(Object::as_name)
+        .map(|subtype| subtype == b"Link")
+        .unwrap_or(false);
+    let action = annot
+        .get(b"A")
+        .ok()
+        .and_then(|x| doc.dereference(x).ok())
+        .and_then(|(_, x)| x.as_dict().ok());
+    is_link
+        && action
+            .and_then(|x| x.get(b"S").and_then(Object::as_name).ok())
+            .map(|s| s == b"URI")
+            .unwrap_or(false)
+}
@@ -0,0 +1,282 @@
+/*!
+ HTTP service for Gulag Cleaner.
+
+ * `POST /clean` takes the PDF as the request body and returns the cleaned PDF. A summary of
+   the report is sent in `X-Gulag-*` headers, and the full report as the first part of a
+   `multipart/mixed` response if the request accepts it. Options are passed in the query string, as in `?force_naive=true`.
+ * `POST /analyze` takes the PDF as the request body and returns the analysis as JSON.
+ * `GET /health` returns `{"status": "ok"}`.
+*/
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{DefaultBodyLimit, Query, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use gulagcleaner_rs::models::method::method_name;
+use gulagcleaner_rs::report::CleanReport;
+use gulagcleaner_rs::{CancellationToken, CleanOptions, Hooks};
+use serde_json::json;
+use tokio::sync::Semaphore;
+
+/// Configuration of the service, from the command line or the environment.
+#[derive(Debug, Clone, clap::Parser)]
+#[command(version, about = "Serves Gulag Cleaner over HTTP.")]
+pub struct Config {
+    /// Address to listen on.
+    #[arg(long, env = "GULAG_ADDR", default_value = "127.0.0.1:8080")]
+    pub addr: SocketAddr,
+    /// Largest request body accepted, in bytes.
+    #[arg(long, env = "GULAG_MAX_BODY_BYTES", default_value_t = 50 * 1024 * 1024)]
+    pub max_body_bytes: usize,
+    /// Number of documents processed at the same time. Further requests wait for a slot.
+    #[arg(long, env = "GULAG_MAX_CONCURRENCY", default_value_t = 4)]
+    pub max_concurrency: usize,
+    /// Seconds a request may take, including the wait for a slot.
+    #[arg(long, env = "GULAG_TIMEOUT_SECS", default_value_t = 60)]
+    pub timeout_secs: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            addr: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            max_body_bytes: 50 * 1024 * 1024,
+            max_concurrency: 4,
+            timeout_secs: 60,
+        }
+    }
+}
+
+struct AppState {
+    slots: Arc<Semaphore>,
+    timeout: Duration,
+}
+
+/// Builds the router of the service.
+pub fn app(config: &Config) -> Router {
+    let state = Arc::new(AppState {
+        slots: Arc::new(Semaphore::new(config.max_concurrency.max(1))),
+        timeout: Duration::from_secs(config.timeout_secs),
+    });
+
+    Router::new()
+        .route("/health", get(health))
+        .route("/clean", post(clean))
+        .route("/analyze", post(analyze))
+        .layer(DefaultBodyLimit::max(config.max_body_bytes))
+        .with_state(state)
+}
+
+/// An error response, as `{"error": ..., "code": ...}`.
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    fn internal(message: impl ToString) -> ApiError {
+        ApiError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            code: "INTERNAL_ERROR",
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<gulagcleaner_rs::Error> for ApiError {
+    fn from(e: gulagcleaner_rs::Error) -> Self {
+        use gulagcleaner_rs::Error;
+        let status = match e {
+            Error::Parse(_)
+            | Error::Encrypted(_)
+            | Error::Unsupported(_)
+            | Error::MissingMetadata { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::InvalidOptions(_) => StatusCode::BAD_REQUEST,
+            Error::Cancelled => StatusCode::GATEWAY_TIMEOUT,
+            Error::LimitExceeded { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            Error::Save(_) | Error::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError {
+            status,
+            code: e.code(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(e: QueryRejection) -> Self {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            code: "INVALID_OPTIONS",
+            message: e.body_text(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = json!({ "error": self.message, "code": self.code });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// Cancels its token when it is dropped.
+struct CancelOnDrop(CancellationToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Runs `job` on the blocking thread pool once a slot is free.
+///
+/// If the request takes longer than the timeout, the job is cancelled between pages and the
+/// request fails with a 504. The job is cancelled the same way when the client disconnects
+/// and the request is dropped. The slot is held until the job has actually stopped.
+async fn run_job<T: Send + 'static>(
+    state: &AppState,
+    job: impl FnOnce(&Hooks) -> gulagcleaner_rs::Result<T> + Send + 'static,
+) -> Result<T, ApiError> {
+    let job_token = CancellationToken::new();
+    let _cancel = CancelOnDrop(job_token.clone());
+    let work = async {
+        let slot = state
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(ApiError::internal)?;
+        tokio::task::spawn_blocking(move || {
+            let _slot = slot;
+            let hooks = Hooks {
+                progress: None,
+                cancel: Some(&job_token),
+            };
+            job(&hooks)
+        })
+        .await
+        .map_err(ApiError::internal)?
+        .map_err(ApiError::from)
+    };
+
+    match tokio::time::timeout(state.timeout, work).await {
+        Ok(result) => result,
+        Err(_) => Err(ApiError {
+            status: StatusCode::GATEWAY_TIMEOUT,
+            code: "TIMEOUT",
+            message: format!("the request took longer than {:?}", state.timeout),
+        }),
+    }
+}
+
+async fn health() -> Json<serde_json::Value> {
+    Json(json!({ "status": "ok", "version": env!("CARGO_PKG_VERSION") }))
+}
+
+async fn clean(
+    State(state): State<Arc<AppState>>,
+    options: Result<Query<CleanOptions>, QueryRejection>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let Query(options) = options?;
+    let (clean_pdf, report) = run_job(&state, move |hooks| {
+        gulagcleaner_rs::clean_pdf_with_hooks(&body, &options, hooks)
+    })
+    .await?;
+
+    let accepts_multipart = headers
+        .get(header::ACCEPT)
+        .and_then(|x| x.to_str().ok())
+        .is_some_and(|x| x.contains("multipart/mixed"));
+    if accepts_multipart {
+        multipart_response(&clean_pdf, &report)
+    } else {
+        pdf_response(clean_pdf, &report)
+    }
+}
+
+/// The cleaned PDF, with a summary of the report in `X-Gulag-*` headers.
+///
+/// The headers have a fixed size, whatever the number of pages and warnings. The full report
+/// is only sent in the `multipart/mixed` response.
+fn pdf_response(clean_pdf: Vec<u8>, report: &CleanReport) -> Result<Response, ApiError> {
+    let header_values = [
+        ("x-gulag-method", report.method_code.to_string()),
+        ("x-gulag-method-name", report.method_name().to_string()),
+        ("x-gulag-status", format!("{:?}", report.status())),
+        ("x-gulag-pages-before", report.pages_before.to_string()),
+        ("x-gulag-pages-after", report.pages_after.to_string()),
+        ("x-gulag-warnings", report.warnings.len().to_string()),
+        (
+            "x-gulag-issues",
+            report.verification.issues.len().to_string(),
+        ),
+    ];
+
+    let mut response = ([(header::CONTENT_TYPE, "application/pdf")], clean_pdf).into_response();
+    for (name, value) in header_values {
+        let value = HeaderValue::from_str(&value).map_err(ApiError::internal)?;
+        response.headers_mut().insert(name, value);
+    }
+    Ok(response)
+}
+
+/// A `multipart/mixed` response with the JSON report followed by the cleaned PDF.
+fn multipart_response(clean_pdf: &[u8], report: &CleanReport) -> Result<Response, ApiError> {
+    let report = serde_json::to_vec(report).map_err(ApiError::internal)?;
+    let mut boundary = String::from("gulagcleaner-report");
+    while contains(clean_pdf, boundary.as_bytes()) || contains(&report, boundary.as_bytes()) {
+        boundary.push('-');
+    }
+
+    let mut body = Vec::with_capacity(clean_pdf.len() + report.len() + 256);
+    body.extend_from_slice(
+        format!("--{}\r\nContent-Type: application/json\r\n\r\n", boundary).as_bytes(),
+    );
+    body.extend_from_slice(&report);
+    body.extend_from_slice(
+        format!(
+            "\r\n--{}\r\nContent-Type: application/pdf\r\n\r\n",
+            boundary
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(clean_pdf);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+    let content_type = format!("multipart/mixed; boundary={}", boundary);
+    Ok(([(header::CONTENT_TYPE, content_type)], body).into_response())
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|x| x == needle)
+}
+
+async fn analyze(
+    State(state): State<Arc<AppState>>,
+    body: Bytes,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let analysis = run_job(&state, move |hooks| {
+        let doc = gulagcleaner_rs::load_document(&body)?;
+        gulagcleaner_rs::analyze_document_with_hooks(&doc, hooks)
+    })
+    .await?;
+
+    let mut value = serde_json::to_value(&analysis).map_err(ApiError::internal)?;
+    value["method_name"] = method_name(analysis.method_code).into();
+    Ok(Json(value))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,16 @@
+use clap::Parser;
+use gulagcleaner_server::{app, Config};
+use tokio::net::TcpListener;
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let config = Config::parse();
+    let listener = TcpListener::bind(config.addr).await?;
+    println!("Listening on http://{}", listener.local_addr()?);
+
+    axum::serve(listener, app(&config))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+}
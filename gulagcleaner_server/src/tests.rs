@@ -0,0 +1,144 @@
+use crate::{app, run_job, AppState, Config};
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use http_body_util::BodyExt;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tower::ServiceExt;
+
+const STUDOCU_PATH: &str = "../gulagcleaner_rs/example_docs/studocu-example.pdf";
+
+/// Sends a request to the service and returns the status, headers and body of the response.
+async fn send(app: Router, request: Request<Body>) -> (StatusCode, header::HeaderMap, Vec<u8>) {
+    let response = app.oneshot(request).await.unwrap();
+    let (parts, body) = response.into_parts();
+    let body = body.collect().await.unwrap().to_bytes().to_vec();
+    (parts.status, parts.headers, body)
+}
+
+fn post(uri: &str, body: Vec<u8>) -> Request<Body> {
+    Request::post(uri).body(Body::from(body)).unwrap()
+}
+
+#[tokio::test]
+async fn test_health() {
+    let request = Request::get("/health").body(Body::empty()).unwrap();
+    let (status, _, body) = send(app(&Config::default()), request).await;
+    assert_eq!(status, StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["status"], "ok");
+}
+
+#[tokio::test]
+async fn test_clean_returns_pdf_and_report_headers() {
+    let data = fs::read(STUDOCU_PATH).expect("Failed to read PDF");
+    let (status, headers, body) = send(app(&Config::default()), post("/clean", data)).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_TYPE], "application/pdf");
+    assert_eq!(headers["x-gulag-method-name"], "StuDocu");
+    assert_eq!(headers["x-gulag-status"], "Passed");
+    assert_eq!(headers["x-gulag-pages-before"], "10");
+    assert_eq!(headers["x-gulag-pages-after"], "9");
+    assert_eq!(headers["x-gulag-warnings"], "0");
+    assert!(!headers.contains_key("x-gulag-report"));
+    assert!(body.starts_with(b"%PDF"));
+}
+
+#[tokio::test]
+async fn test_clean_returns_multipart() {
+    let data = fs::read(STUDOCU_PATH).expect("Failed to read PDF");
+    let request = Request::post("/clean?force_naive=false")
+        .header(header::ACCEPT, "multipart/mixed")
+        .body(Body::from(data))
+        .unwrap();
+    let (status, headers, body) = send(app(&Config::default()), request).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let content_type = headers[header::CONTENT_TYPE].to_str().unwrap();
+    let boundary = content_type.split("boundary=").nth(1).unwrap();
+    let body = String::from_utf8_lossy(&body);
+    let parts: Vec<&str> = body.split(&format!("--{}", boundary)).collect();
+    assert!(parts[1].contains("application/json"));
+    assert!(parts[1].contains("\"deleted_pages\":[1]"));
+    assert!(parts[2].contains("application/pdf\r\n\r\n%PDF"));
+}
+
+#[tokio::test]
+async fn test_analyze() {
+    let data = fs::read(STUDOCU_PATH).expect("Failed to read PDF");
+    let (status, _, body) = send(app(&Config::default()), post("/analyze", data)).await;
+    assert_eq!(status, StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["method_name"], "StuDocu");
+    assert_eq!(body["pages"][0]["delete"], true);
+}
+
+#[tokio::test]
+async fn test_errors_and_limits() {
+    let config = Config {
+        max_body_bytes: 1024,
+        ..Default::default()
+    };
+
+    let (status, _, body) = send(app(&config), post("/clean", b"not a pdf".to_vec())).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], "PARSE_ERROR");
+
+    let (status, _, body) = send(app(&config), post("/clean?force_naive=yes", vec![])).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], "INVALID_OPTIONS");
+
+    let uri = format!("/clean?mod_date={}", i64::MAX);
+    let (status, _, body) = send(app(&config), post(&uri, b"%PDF".to_vec())).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], "INVALID_OPTIONS");
+
+    let (status, _, _) = send(app(&config), post("/clean", vec![0; 2048])).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_timeout() {
+    let data = fs::read(STUDOCU_PATH).expect("Failed to read PDF");
+    let config = Config {
+        timeout_secs: 0,
+        ..Default::default()
+    };
+    for uri in ["/clean", "/analyze"] {
+        let (status, _, body) = send(app(&config), post(uri, data.clone())).await;
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "TIMEOUT");
+    }
+}
+
+#[tokio::test]
+async fn test_dropped_request_cancels_its_job() {
+    let state = AppState {
+        slots: Arc::new(Semaphore::new(1)),
+        timeout: Duration::from_secs(60),
+    };
+    // A job that runs for 20 seconds unless it is cancelled
+    let job = run_job(&state, |hooks| {
+        for _ in 0..20_000 {
+            if hooks.cancel.is_some_and(|x| x.is_cancelled()) {
+                return Err(gulagcleaner_rs::Error::Cancelled);
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        Ok(())
+    });
+    // The client goes away while the job is running
+    let dropped = tokio::time::timeout(Duration::from_millis(50), job).await;
+    assert!(dropped.is_err());
+
+    let slot = tokio::time::timeout(Duration::from_secs(5), state.slots.acquire()).await;
+    assert!(slot.is_ok(), "the job kept its slot");
+}
@@ -1,21 +1,233 @@
-use gulagcleaner_rs;
-use serde::{Deserialize, Serialize};
+use gulagcleaner_rs::models::method::method_name;
+use gulagcleaner_rs::{
+    analyze_document, clean_pdf_with_hooks, load_document, CancellationToken, CleanOptions, Hooks,
+    Progress, Stage,
+};
+use js_sys::{Object, Reflect, Uint8Array};
+use std::cell::RefCell;
 use wasm_bindgen::prelude::*;
 
-#[derive(Serialize, Deserialize)]
-pub struct CleaningResult {
-    result: Vec<u8>,
-    method: u8,
+/// Reports any remaining Rust panic to the browser console instead of a bare "unreachable".
+#[wasm_bindgen(start)]
+pub fn start() {
+    console_error_panic_hook::set_once();
 }
 
+/// Converts an error into a JS `Error` named "GulagError" with a `code` property.
+fn js_error(message: &str, code: &str) -> JsValue {
+    let error = js_sys::Error::new(message);
+    error.set_name("GulagError");
+    let _ = Reflect::set(&error, &"code".into(), &code.into());
+    error.into()
+}
+
+fn clean_error(e: gulagcleaner_rs::Error) -> JsValue {
+    js_error(&e.to_string(), e.code())
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const REPORT_TS_TYPES: &'static str = include_str!("report.d.ts");
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &'static str = r#"
+export type Stage = "Classifying" | "Applying" | "Verifying";
+
+/** Called before each page is processed, e.g. ("Classifying", 12, 300). */
+export type ProgressCallback = (stage: Stage, page: number, total: number) => void;
+
+export type PageType = "BannerAds" | "FullPageAds" | "Watermark" | "Idk";
+
+export interface PageAnalysis {
+  page: number;
+  content_streams: number;
+  page_type: PageType;
+  /** Whether the detected method would delete the page. */
+  delete: boolean;
+}
+
+export interface Analysis {
+  /** 0 for "Wuolah", 1 for "StuDocu", and 2 for "Naive". */
+  method_code: number;
+  method_name: "Wuolah" | "StuDocu" | "Naive";
+  pages: PageAnalysis[];
+}
+
+export interface DocumentMetadata {
+  file_name: string;
+  author: string;
+  subject: string;
+  course: string;
+  faculty: string;
+  university: string;
+}
+
+export interface CleaningResult {
+  /** The cleaned PDF document. */
+  data: Uint8Array;
+  /** 0 for "Wuolah", 1 for "StuDocu", and 2 for "Naive". */
+  method: number;
+  method_name: "Wuolah" | "StuDocu" | "Naive";
+  report: CleanReport;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "CleaningResult")]
+    pub type CleaningResult;
+
+    #[wasm_bindgen(typescript_type = "CleanOptions")]
+    pub type JsCleanOptions;
+
+    #[wasm_bindgen(typescript_type = "ProgressCallback")]
+    pub type ProgressCallback;
+
+    #[wasm_bindgen(typescript_type = "AbortSignal")]
+    pub type AbortSignal;
+
+    #[wasm_bindgen(method, getter)]
+    fn aborted(this: &AbortSignal) -> bool;
+
+    #[wasm_bindgen(typescript_type = "Analysis")]
+    pub type JsAnalysis;
+
+    #[wasm_bindgen(typescript_type = "DocumentMetadata")]
+    pub type JsDocumentMetadata;
+}
+
+/// Cleans a PDF document.
+///
+/// Throws a `GulagError` if the document can't be cleaned.
+#[wasm_bindgen]
+pub fn clean_pdf(data: Vec<u8>, force_naive: bool) -> Result<CleaningResult, JsValue> {
+    let options = CleanOptions {
+        force_naive,
+        ..Default::default()
+    };
+    clean(&data, &options, &Hooks::default())
+}
+
+/// Cleans a PDF document with the given options. Missing options take their default value.
+///
+/// `on_progress` is called before each page is classified, applied and verified. The run
+/// stops between pages once `signal` is aborted, for example from the callback itself. If the
+/// callback throws, the run stops and the error is rethrown.
+///
+/// Throws a `GulagError` if the options are invalid, the document can't be cleaned, or the
+/// run was aborted (with code "CANCELLED").
 #[wasm_bindgen]
-pub fn clean_pdf(data: Vec<u8>, force_naive: bool) -> Vec<u8> {
-    let (clean_pdf, _) = gulagcleaner_rs::clean_pdf(data, force_naive);
-    // For some reason, the serde serialization messes up our data, so we just return the raw bytes until we find a fix
-    // let example = CleaningResult {
-    //     result: clean_pdf,
-    //     method: method_code,
-    // };
-    // Ok(serde_wasm_bindgen::to_value(&example)?)
-    return clean_pdf;
+pub fn clean_with_options(
+    data: Vec<u8>,
+    options: Option<JsCleanOptions>,
+    on_progress: Option<ProgressCallback>,
+    signal: Option<AbortSignal>,
+) -> Result<CleaningResult, JsValue> {
+    let options: CleanOptions = match options {
+        Some(x) => serde_wasm_bindgen::from_value(x.into())
+            .map_err(|e| js_error(&e.to_string(), "INVALID_OPTIONS"))?,
+        None => CleanOptions::default(),
+    };
+
+    let progress = JsProgress {
+        callback: on_progress.map(|x| x.unchecked_into()),
+        signal,
+        token: CancellationToken::new(),
+        thrown: RefCell::new(None),
+    };
+    if progress.is_aborted() {
+        return Err(clean_error(gulagcleaner_rs::Error::Cancelled));
+    }
+    let hooks = Hooks {
+        progress: Some(&progress),
+        cancel: Some(&progress.token),
+    };
+    let result = clean(&data, &options, &hooks);
+    match progress.thrown.into_inner() {
+        Some(thrown) => Err(thrown),
+        None => result,
+    }
+}
+
+/// Forwards progress to a JS callback and turns an aborted `AbortSignal` into a cancellation.
+///
+/// The cleaning runs synchronously, so the signal is checked after each callback.
+struct JsProgress {
+    callback: Option<js_sys::Function>,
+    signal: Option<AbortSignal>,
+    token: CancellationToken,
+    thrown: RefCell<Option<JsValue>>,
+}
+
+impl JsProgress {
+    fn is_aborted(&self) -> bool {
+        self.signal.as_ref().is_some_and(AbortSignal::aborted)
+    }
+}
+
+impl Progress for JsProgress {
+    fn page(&self, stage: Stage, page: u32, total: u32) {
+        if let Some(callback) = &self.callback {
+            let stage = format!("{:?}", stage);
+            if let Err(e) =
+                callback.call3(&JsValue::NULL, &stage.into(), &page.into(), &total.into())
+            {
+                self.thrown.replace(Some(e));
+                self.token.cancel();
+            }
+        }
+        if self.is_aborted() {
+            self.token.cancel();
+        }
+    }
 }
+
+fn clean(data: &[u8], options: &CleanOptions, hooks: &Hooks) -> Result<CleaningResult, JsValue> {
+    let (clean_pdf, report) = clean_pdf_with_hooks(data, options, hooks).map_err(clean_error)?;
+
+    // The bytes are copied straight into a Uint8Array; going through serde would turn them
+    // into a plain array of numbers.
+    let result = Object::new();
+    Reflect::set(
+        &result,
+        &"data".into(),
+        &Uint8Array::from(clean_pdf.as_slice()),
+    )?;
+    Reflect::set(&result, &"method".into(), &report.method_code.into())?;
+    Reflect::set(&result, &"method_name".into(), &report.method_name().into())?;
+    Reflect::set(
+        &result,
+        &"report".into(),
+        &serde_wasm_bindgen::to_value(&report)?,
+    )?;
+    Ok(result.unchecked_into())
+}
+
+/// Detects the cleaning method of a PDF document and classifies its pages, without cleaning it.
+///
+/// Throws a `GulagError` if the document can't be parsed.
+#[wasm_bindgen]
+pub fn analyze(data: Vec<u8>) -> Result<JsAnalysis, JsValue> {
+    let doc = load_document(&data).map_err(clean_error)?;
+    let analysis = analyze_document(&doc);
+
+    let result = serde_wasm_bindgen::to_value(&analysis)?;
+    Reflect::set(
+        &result,
+        &"method_name".into(),
+        &method_name(analysis.method_code).into(),
+    )?;
+    Ok(result.unchecked_into())
+}
+
+/// Reads the metadata printed on the cover page of a PDF document, without cleaning it.
+///
+/// Throws a `GulagError` if the document can't be parsed or has no cover metadata.
+#[wasm_bindgen]
+pub fn extract_metadata(data: Vec<u8>) -> Result<JsDocumentMetadata, JsValue> {
+    let doc = load_document(&data).map_err(clean_error)?;
+    let metadata = gulagcleaner_rs::extract_metadata(&doc).map_err(clean_error)?;
+    Ok(serde_wasm_bindgen::to_value(&metadata)?.unchecked_into())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,75 @@
+// The report and options types shared by the WASM and Node.js packages. The Node.js
+// package has a copy in gulagcleaner_node/report.d.ts, which must be kept identical.
+
+export type VerifyStatus = "Passed" | "Degraded" | "Failed";
+
+export type VerifyIssue =
+  | { kind: "Unparseable"; reason: string }
+  | { kind: "PageCountMismatch"; expected: number; found: number }
+  | { kind: "NoPagesLeft" }
+  | { kind: "EmptyContents"; page: number }
+  | { kind: "UndecodableContents"; page: number; reason: string }
+  | { kind: "MissingResource"; page: number; category: string; name: string }
+  | { kind: "AdSignature"; page: number; signature: string };
+
+export interface Verification {
+  status: VerifyStatus;
+  issues: VerifyIssue[];
+}
+
+export interface TextComparison {
+  words_before: number;
+  words_after: number;
+  words_lost: number;
+}
+
+export type Warning =
+  | { kind: "PageSkipped"; page: number; reason: string }
+  | { kind: "PageWithoutImages"; page: number }
+  | { kind: "PageOutOfRange"; page: number; page_count: number }
+  | { kind: "TextLoss"; lost_ratio: number; threshold: number };
+
+export interface CleanReport {
+  /** 0 for "Wuolah", 1 for "StuDocu", and 2 for "Naive". */
+  method_code: number;
+  pages_before: number;
+  pages_after: number;
+  deleted_pages: number[];
+  verification: Verification;
+  text: TextComparison;
+  warnings: Warning[];
+}
+
+/** The error thrown when a document can't be cleaned. */
+export interface GulagError extends Error {
+  name: "GulagError";
+  code:
+    | "PARSE_ERROR"
+    | "ENCRYPTED_DOCUMENT"
+    | "UNSUPPORTED_DOCUMENT"
+    | "SAVE_ERROR"
+    | "IO_ERROR"
+    | "METADATA_NOT_FOUND"
+    | "INVALID_OPTIONS"
+    | "CANCELLED"
+    | "LIMIT_EXCEEDED";
+}
+
+/** Bounds on the size of a document. Going over one throws with code "LIMIT_EXCEEDED". */
+export interface Limits {
+  max_pages?: number;
+  max_objects?: number;
+  max_decompressed_bytes?: number;
+  max_stream_bytes?: number;
+}
+
+export interface CleanOptions {
+  /** Use the naive cleaning method instead of detecting the platform. */
+  force_naive?: boolean;
+  /** Fraction of non-ad text that may be lost before a "TextLoss" warning is raised. */
+  text_loss_threshold?: number;
+  limits?: Limits;
+  /** Unix time to set as the modification date. The original date is kept if unset, and a
+   * date out of range throws with code "INVALID_OPTIONS". */
+  mod_date?: number;
+}
@@ -0,0 +1,10 @@
+/// The Node.js package ships a copy of the shared TypeScript types, which must not drift
+/// from the ones generated into the WASM package. `npm run build` in `gulagcleaner_node`
+/// copies them again.
+#[test]
+fn test_node_report_types_match() {
+    assert!(
+        include_str!("report.d.ts") == include_str!("../../gulagcleaner_node/report.d.ts"),
+        "gulagcleaner_node/report.d.ts differs from gulagcleaner_wasm/src/report.d.ts"
+    );
+}